@@ -1,7 +1,7 @@
-import { createResource, createMemo, Show } from 'solid-js';
+import { createResource, createMemo, onCleanup, Show } from 'solid-js';
 import type { Component } from 'solid-js';
 
-type Adhan = "Fajr" | "Dhuhr" | "Asr" | "Maghrib" | "Isha"
+type Adhan = "Imsak" | "Fajr" | "Sunrise" | "Dhuhr" | "Asr" | "Maghrib" | "Isha" | "Midnight" | "LastThird"
 interface Prayer {
   date: string              // "2022-12-29"
   timestamp: number         // 1672257661
@@ -55,6 +55,14 @@ const togglePrayerAdhan = async (date: string, adhan: Adhan, play_adhan: boolean
 const App: Component = () => {
   const [prayersResponse, { mutate, refetch }] = createResource<Prayer[]>(async () => (await fetch(`/timings`)).json());
 
+  // reload timings when the server pushes a change
+  const events = new EventSource('/events');
+  events.onmessage = ({ data }) => {
+    const { type } = JSON.parse(data);
+    if (type === 'timings_refreshed') refetch();
+  };
+  onCleanup(() => events.close());
+
   const prayers = createMemo(() => flattenPrayers(prayersResponse() ?? []));
   const nextPrayerIndex = createMemo(() => prayers().findIndex(({ datetime }) => datetime >= new Date()));
   const month = createMemo(() => {
@@ -87,6 +95,18 @@ const App: Component = () => {
         >
           TEST
         </button>
+        <button
+          class="pause"
+          on:click={() => fetch('/pause', { method: 'POST' })}
+        >
+          PAUSE
+        </button>
+        <button
+          class="resume"
+          on:click={() => fetch('/resume', { method: 'POST' })}
+        >
+          RESUME
+        </button>
         <button
           class="halt"
           on:click={() => fetch('/halt', { method: 'POST' })}
@@ -123,7 +143,7 @@ const App: Component = () => {
           </tr>
           {prayers().map(({ date, adhan, datetime, play_adhan }, index) => (
             <>
-              <Show when={adhan === 'Fajr'}><div style={{ display: 'flex', "font-weight": 500, color: 'deeppink' }}>{datetime.toLocaleString('en-US', { weekday: 'long' })}</div></Show>
+              <Show when={index === 0 || prayers()[index - 1].date !== date}><div style={{ display: 'flex', "font-weight": 500, color: 'deeppink' }}>{datetime.toLocaleString('en-US', { weekday: 'long' })}</div></Show>
               <tr
                 class:endrow={index === prayers().length - 1}
                 class:isnext={index === nextPrayerIndex()}
@@ -142,7 +162,7 @@ const App: Component = () => {
                   </button>
                 </td>
               </tr>
-              <Show when={adhan === 'Isha'}><div style={{ display: 'flex' }}><hr /></div></Show>
+              <Show when={index === prayers().length - 1 || prayers()[index + 1].date !== date}><div style={{ display: 'flex' }}><hr /></div></Show>
             </>
           ))}
         </table>
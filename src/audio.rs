@@ -0,0 +1,362 @@
+// Audio output behind a trait, so the player runs without a sound card (and can be tested).
+//
+// `RodioBackend` plays through the system's audio device; `NullBackend` discards everything and
+// `RecordingBackend` keeps a log of what was played, at what volume and for how long.
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+use serde::{Deserialize, Serialize};
+use std::io::BufReader;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    #[default]
+    Rodio,
+    Null,
+}
+
+// sound file to play
+pub struct Track {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+pub trait AudioBackend: Sync + Send {
+    // start playing tracks one after another, fading in the first
+    fn play(&self, tracks: Vec<Track>, volume: f32, fade_in: Duration) -> Result<Output, String>;
+}
+
+// controls of a running playback (a `Sink` in rodio's terms)
+pub trait Playback: Sync + Send {
+    fn volume(&self) -> f32;
+    fn set_volume(&self, volume: f32);
+    fn pause(&self);
+    fn play(&self);
+    fn is_paused(&self) -> bool;
+    fn stop(&self);
+    // true once every track has been played (or playback was stopped)
+    fn empty(&self) -> bool;
+    fn sleep_until_end(&self);
+}
+
+pub struct Output {
+    pub playback: Arc<dyn Playback>,
+    // output stream, kept open while playing
+    _device: Option<Box<dyn std::any::Any>>,
+}
+
+pub struct RodioBackend {
+    // output device name; system default if unset
+    pub device: Option<String>,
+}
+
+// open configured output device, falling back to the system default
+fn output_stream(device: Option<&str>) -> Result<(OutputStream, OutputStreamHandle), String> {
+    use rodio::cpal::traits::{DeviceTrait, HostTrait};
+
+    if let Some(name) = device {
+        let device = rodio::cpal::default_host()
+            .output_devices()
+            .ok()
+            .and_then(|mut devices| devices.find(|d| d.name().map(|n| n == name).unwrap_or(false)));
+        match device.map(|device| OutputStream::try_from_device(&device)) {
+            Some(Ok(stream)) => return Ok(stream),
+            Some(Err(e)) => tracing::error!("error opening audio device {:?}: {:?}", name, e),
+            None => tracing::error!("audio device {:?} not found, using default", name),
+        }
+    }
+    OutputStream::try_default().map_err(|e| format!("Error opening audio output: {:?}", e))
+}
+
+impl AudioBackend for RodioBackend {
+    fn play(&self, tracks: Vec<Track>, volume: f32, fade_in: Duration) -> Result<Output, String> {
+        let (stream, stream_handle) = output_stream(self.device.as_deref())?;
+        let sink = Sink::try_new(&stream_handle)
+            .map_err(|e| format!("Error opening audio sink: {:?}", e))?;
+
+        let mut decoded = 0;
+        for track in tracks {
+            let source = match Decoder::new(BufReader::new(std::io::Cursor::new(track.data))) {
+                Ok(source) => source,
+                Err(e) => {
+                    tracing::error!("error decoding sound {:?}: {:?}", track.name, e);
+                    continue;
+                }
+            };
+            if decoded == 0 && !fade_in.is_zero() {
+                sink.append(source.fade_in(fade_in));
+            } else {
+                sink.append(source);
+            }
+            decoded += 1;
+        }
+        if decoded == 0 {
+            return Err("no sound could be decoded".to_owned());
+        }
+        sink.set_volume(volume);
+
+        Ok(Output {
+            playback: Arc::new(sink),
+            _device: Some(Box::new(stream)),
+        })
+    }
+}
+
+impl Playback for Sink {
+    fn volume(&self) -> f32 {
+        Sink::volume(self)
+    }
+    fn set_volume(&self, volume: f32) {
+        Sink::set_volume(self, volume)
+    }
+    fn pause(&self) {
+        Sink::pause(self)
+    }
+    fn play(&self) {
+        Sink::play(self)
+    }
+    fn is_paused(&self) -> bool {
+        Sink::is_paused(self)
+    }
+    fn stop(&self) {
+        Sink::stop(self)
+    }
+    fn empty(&self) -> bool {
+        Sink::empty(self)
+    }
+    fn sleep_until_end(&self) {
+        Sink::sleep_until_end(self)
+    }
+}
+
+// plays nothing; playback ends right away
+pub struct NullBackend;
+
+struct NullPlayback {
+    volume: Mutex<f32>,
+}
+
+impl AudioBackend for NullBackend {
+    fn play(&self, tracks: Vec<Track>, volume: f32, _: Duration) -> Result<Output, String> {
+        for track in tracks {
+            tracing::info!("no audio output, skipping {}", track.name);
+        }
+        Ok(Output {
+            playback: Arc::new(NullPlayback {
+                volume: Mutex::new(volume),
+            }),
+            _device: None,
+        })
+    }
+}
+
+impl Playback for NullPlayback {
+    fn volume(&self) -> f32 {
+        *self.volume.lock().unwrap()
+    }
+    fn set_volume(&self, volume: f32) {
+        *self.volume.lock().unwrap() = volume;
+    }
+    fn pause(&self) {}
+    fn play(&self) {}
+    fn is_paused(&self) -> bool {
+        false
+    }
+    fn stop(&self) {}
+    fn empty(&self) -> bool {
+        true
+    }
+    fn sleep_until_end(&self) {}
+}
+
+// what a playback of the recording backend did
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Recording {
+    pub tracks: Vec<String>,
+    pub fade_in: Duration,
+    // initial volume followed by every change
+    pub volumes: Vec<f32>,
+    pub paused: bool,
+    pub stopped: bool,
+    // time played, excluding pauses; set when playback ends
+    pub played: Option<Duration>,
+}
+
+// pretends every playback lasts `length` (unless stopped) and records it
+pub struct RecordingBackend {
+    length: Duration,
+    recordings: Mutex<Vec<Arc<Mutex<Recording>>>>,
+}
+
+impl RecordingBackend {
+    pub fn new(length: Duration) -> Self {
+        Self {
+            length,
+            recordings: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn recordings(&self) -> Vec<Recording> {
+        self.recordings
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|recording| recording.lock().unwrap().clone())
+            .collect()
+    }
+}
+
+impl AudioBackend for RecordingBackend {
+    fn play(&self, tracks: Vec<Track>, volume: f32, fade_in: Duration) -> Result<Output, String> {
+        let recording = Arc::new(Mutex::new(Recording {
+            tracks: tracks.into_iter().map(|track| track.name).collect(),
+            fade_in,
+            volumes: vec![volume],
+            ..Recording::default()
+        }));
+        self.recordings.lock().unwrap().push(Arc::clone(&recording));
+
+        Ok(Output {
+            playback: Arc::new(RecordingPlayback {
+                recording,
+                length: self.length,
+                clock: Mutex::new(PlayClock {
+                    volume,
+                    played: Duration::ZERO,
+                    since: Some(Instant::now()),
+                    done: false,
+                }),
+                changed: Condvar::new(),
+            }),
+            _device: None,
+        })
+    }
+}
+
+struct PlayClock {
+    volume: f32,
+    // played before `since`
+    played: Duration,
+    // start of the current stretch of playing; none while paused
+    since: Option<Instant>,
+    done: bool,
+}
+
+impl PlayClock {
+    fn played(&self) -> Duration {
+        self.played + self.since.map(|since| since.elapsed()).unwrap_or_default()
+    }
+}
+
+struct RecordingPlayback {
+    recording: Arc<Mutex<Recording>>,
+    length: Duration,
+    clock: Mutex<PlayClock>,
+    changed: Condvar,
+}
+
+impl RecordingPlayback {
+    fn finish(&self, clock: &mut PlayClock) {
+        if !clock.done {
+            clock.done = true;
+            self.recording.lock().unwrap().played = Some(clock.played().min(self.length));
+            self.changed.notify_all();
+        }
+    }
+}
+
+impl Playback for RecordingPlayback {
+    fn volume(&self) -> f32 {
+        self.clock.lock().unwrap().volume
+    }
+    fn set_volume(&self, volume: f32) {
+        self.clock.lock().unwrap().volume = volume;
+        self.recording.lock().unwrap().volumes.push(volume);
+    }
+    fn pause(&self) {
+        let mut clock = self.clock.lock().unwrap();
+        if let Some(since) = clock.since.take() {
+            clock.played += since.elapsed();
+            self.recording.lock().unwrap().paused = true;
+        }
+    }
+    fn play(&self) {
+        let mut clock = self.clock.lock().unwrap();
+        if clock.since.is_none() {
+            clock.since = Some(Instant::now());
+            self.changed.notify_all();
+        }
+    }
+    fn is_paused(&self) -> bool {
+        self.clock.lock().unwrap().since.is_none()
+    }
+    fn stop(&self) {
+        let mut clock = self.clock.lock().unwrap();
+        if !clock.done {
+            self.recording.lock().unwrap().stopped = true;
+        }
+        self.finish(&mut clock);
+    }
+    fn empty(&self) -> bool {
+        let clock = self.clock.lock().unwrap();
+        clock.done || clock.played() >= self.length
+    }
+    fn sleep_until_end(&self) {
+        let mut clock = self.clock.lock().unwrap();
+        while !clock.done {
+            let remaining = self.length.saturating_sub(clock.played());
+            if remaining.is_zero() {
+                self.finish(&mut clock);
+            } else if clock.since.is_none() {
+                clock = self.changed.wait(clock).unwrap();
+            } else {
+                clock = self.changed.wait_timeout(clock, remaining).unwrap().0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(name: &str) -> Track {
+        Track {
+            name: name.to_owned(),
+            data: vec![],
+        }
+    }
+
+    #[test]
+    fn test_recording_backend() {
+        let backend = RecordingBackend::new(Duration::from_millis(50));
+        let output = backend
+            .play(vec![track("a.mp3"), track("b.mp3")], 5.0, Duration::ZERO)
+            .unwrap();
+        output.playback.set_volume(6.0);
+        output.playback.sleep_until_end();
+        assert!(output.playback.empty());
+
+        // paused time does not count; stopping ends playback early
+        let output = backend
+            .play(vec![track("c.mp3")], 3.0, Duration::from_secs(2))
+            .unwrap();
+        output.playback.pause();
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(!output.playback.empty());
+        output.playback.play();
+        output.playback.stop();
+        output.playback.sleep_until_end();
+
+        let recordings = backend.recordings();
+        assert_eq!(recordings[0].tracks, vec!["a.mp3", "b.mp3"]);
+        assert_eq!(recordings[0].volumes, vec![5.0, 6.0]);
+        assert_eq!(recordings[0].played, Some(Duration::from_millis(50)));
+        assert!(!recordings[0].stopped);
+
+        assert_eq!(recordings[1].fade_in, Duration::from_secs(2));
+        assert!(recordings[1].paused && recordings[1].stopped);
+        assert!(recordings[1].played.unwrap() < Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,405 @@
+// Offline astronomical prayer time calculation.
+//
+// Port of the well known praytimes.org algorithm, which is also what api.aladhan.com uses under the
+// hood; method ids match the aladhan `method` query param so results can be compared 1:1 with
+// saved api responses.
+use chrono::{Datelike, NaiveDate, NaiveTime, Timelike};
+
+// sun altitude (degrees below horizon) at sunrise/sunset; accounts for refraction and sun radius
+const RISE_SET_ANGLE: f64 = 0.833;
+
+// default imsak is 10 minutes before fajr (same as aladhan)
+const IMSAK_MINUTES: f64 = 10.0;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Twilight {
+    // sun angle below the horizon (degrees)
+    Angle(f64),
+    // fixed minutes after sunset/maghrib
+    Minutes(f64),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Midnight {
+    // mid point between sunset and sunrise
+    Standard,
+    // mid point between sunset and fajr
+    Jafari,
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum School {
+    // asr when shadow length equals object length (aladhan school=0)
+    #[default]
+    Shafi,
+    // asr when shadow length is twice object length (aladhan school=1)
+    Hanafi,
+}
+
+impl School {
+    pub fn id(&self) -> u8 {
+        match self {
+            Self::Shafi => 0,
+            Self::Hanafi => 1,
+        }
+    }
+    fn asr_factor(&self) -> f64 {
+        match self {
+            Self::Shafi => 1.0,
+            Self::Hanafi => 2.0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Method {
+    pub id: u8,
+    pub name: &'static str,
+    pub fajr: f64,
+    pub isha: Twilight,
+    pub maghrib: Twilight,
+    pub midnight: Midnight,
+}
+
+impl Method {
+    // calculation methods by aladhan method id - see https://aladhan.com/calculation-methods
+    pub fn from_id(id: u8) -> Option<Self> {
+        use Twilight::{Angle, Minutes};
+
+        #[rustfmt::skip]
+        let (name, fajr, isha, maghrib, midnight) = match id {
+            0 => ("Shia Ithna-Ansari", 16.0, Angle(14.0), Angle(4.0), Midnight::Jafari),
+            1 => ("University of Islamic Sciences, Karachi", 18.0, Angle(18.0), Minutes(0.0), Midnight::Standard),
+            2 => ("Islamic Society of North America", 15.0, Angle(15.0), Minutes(0.0), Midnight::Standard),
+            3 => ("Muslim World League", 18.0, Angle(17.0), Minutes(0.0), Midnight::Standard),
+            4 => ("Umm Al-Qura University, Makkah", 18.5, Minutes(90.0), Minutes(0.0), Midnight::Standard),
+            5 => ("Egyptian General Authority of Survey", 19.5, Angle(17.5), Minutes(0.0), Midnight::Standard),
+            7 => ("Institute of Geophysics, University of Tehran", 17.7, Angle(14.0), Angle(4.5), Midnight::Jafari),
+            8 => ("Gulf Region", 19.5, Minutes(90.0), Minutes(0.0), Midnight::Standard),
+            9 => ("Kuwait", 18.0, Angle(17.5), Minutes(0.0), Midnight::Standard),
+            10 => ("Qatar", 18.0, Minutes(90.0), Minutes(0.0), Midnight::Standard),
+            11 => ("Majlis Ugama Islam Singapura, Singapore", 20.0, Angle(18.0), Minutes(0.0), Midnight::Standard),
+            12 => ("Union Organization islamic de France", 12.0, Angle(12.0), Minutes(0.0), Midnight::Standard),
+            13 => ("Diyanet İşleri Başkanlığı, Turkey", 18.0, Angle(17.0), Minutes(0.0), Midnight::Standard),
+            14 => ("Spiritual Administration of Muslims of Russia", 16.0, Angle(15.0), Minutes(0.0), Midnight::Standard),
+            // seasonal adjustments of the moonsighting committee are not modelled; angles only
+            15 => ("Moonsighting Committee Worldwide", 18.0, Angle(18.0), Minutes(0.0), Midnight::Standard),
+            16 => ("Dubai", 18.2, Angle(18.2), Minutes(0.0), Midnight::Standard),
+            17 => ("Jabatan Kemajuan Islam Malaysia (JAKIM)", 20.0, Angle(18.0), Minutes(0.0), Midnight::Standard),
+            18 => ("Tunisia", 18.0, Angle(18.0), Minutes(0.0), Midnight::Standard),
+            19 => ("Algeria", 18.0, Angle(17.0), Minutes(0.0), Midnight::Standard),
+            20 => ("Kementerian Agama Republik Indonesia", 20.0, Angle(18.0), Minutes(0.0), Midnight::Standard),
+            21 => ("Morocco", 19.0, Angle(17.0), Minutes(0.0), Midnight::Standard),
+            22 => ("Comunidade Islamica de Lisboa", 18.0, Minutes(77.0), Minutes(3.0), Midnight::Standard),
+            23 => ("Ministry of Awqaf, Islamic Affairs and Holy Places, Jordan", 18.0, Angle(18.0), Minutes(5.0), Midnight::Standard),
+            _ => return None,
+        };
+
+        Some(Self {
+            id,
+            name,
+            fajr,
+            isha,
+            maghrib,
+            midnight,
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Timings {
+    pub imsak: NaiveTime,
+    pub fajr: NaiveTime,
+    pub sunrise: NaiveTime,
+    pub dhuhr: NaiveTime,
+    pub asr: NaiveTime,
+    pub sunset: NaiveTime,
+    pub maghrib: NaiveTime,
+    pub isha: NaiveTime,
+    pub midnight: NaiveTime,
+    pub firstthird: NaiveTime,
+    pub lastthird: NaiveTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct Calculator {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub method: Method,
+    pub school: School,
+    pub offsets: (i8, i8, i8, i8, i8), // fajr, dhuhr, asr, maghrib, isha
+}
+
+impl Calculator {
+    pub fn new(latitude: f64, longitude: f64, method: u8) -> Option<Self> {
+        Some(Self {
+            latitude,
+            longitude,
+            method: Method::from_id(method)?,
+            school: School::default(),
+            offsets: (0, 0, 0, 0, 0),
+        })
+    }
+
+    // calculate timings for date in local time of the given utc offset (in seconds, e.g. +13h for NZDT);
+    // returns None if the sun does not rise/set on that date at this latitude
+    pub fn timings(&self, date: NaiveDate, utc_offset_secs: i32) -> Option<Timings> {
+        let timezone = utc_offset_secs as f64 / 3600.0;
+        let jdate = julian_date(date) - self.longitude / (15.0 * 24.0);
+
+        // initial guesses (hours) as per praytimes.org; a single pass is accurate to the minute
+        let sun = |angle: f64, guess: f64, ccw: bool| {
+            self.sun_angle_time(jdate, angle, guess / 24.0, ccw)
+        };
+        let isha_angle = match self.method.isha {
+            Twilight::Angle(angle) => angle,
+            Twilight::Minutes(_) => 0.0,
+        };
+        let maghrib_angle = match self.method.maghrib {
+            Twilight::Angle(angle) => angle,
+            Twilight::Minutes(_) => 0.0,
+        };
+
+        let mut fajr = sun(self.method.fajr, 5.0, true);
+        let mut sunrise = sun(RISE_SET_ANGLE, 6.0, true);
+        let mut dhuhr = self.mid_day(jdate, 12.0 / 24.0);
+        let mut asr = self.asr_time(jdate, 13.0 / 24.0);
+        let mut sunset = sun(RISE_SET_ANGLE, 18.0, false);
+        let mut maghrib = sun(maghrib_angle, 18.0, false);
+        let mut isha = sun(isha_angle, 18.0, false);
+
+        // convert from local solar time to the timezone
+        for time in [
+            &mut fajr,
+            &mut sunrise,
+            &mut dhuhr,
+            &mut asr,
+            &mut sunset,
+            &mut maghrib,
+            &mut isha,
+        ] {
+            *time += timezone - self.longitude / 15.0;
+        }
+
+        if sunrise.is_nan() || sunset.is_nan() {
+            return None;
+        }
+
+        // high latitude adjustment (aladhan default: ANGLE_BASED)
+        let night = time_diff(sunset, sunrise);
+        fajr = adjust_high_latitude(fajr, sunrise, self.method.fajr, night, true);
+        if let Twilight::Angle(angle) = self.method.isha {
+            isha = adjust_high_latitude(isha, sunset, angle, night, false);
+        }
+        if let Twilight::Angle(angle) = self.method.maghrib {
+            maghrib = adjust_high_latitude(maghrib, sunset, angle, night, false);
+        }
+
+        if let Twilight::Minutes(minutes) = self.method.maghrib {
+            maghrib = sunset + minutes / 60.0;
+        }
+        if let Twilight::Minutes(minutes) = self.method.isha {
+            isha = maghrib + minutes / 60.0;
+        }
+        let imsak = fajr - IMSAK_MINUTES / 60.0;
+
+        let midnight = match self.method.midnight {
+            Midnight::Standard => sunset + time_diff(sunset, sunrise) / 2.0,
+            Midnight::Jafari => sunset + time_diff(sunset, fajr) / 2.0,
+        };
+        let firstthird = sunset + night / 3.0;
+        let lastthird = sunset + night * 2.0 / 3.0;
+
+        // apply per prayer tuning (minutes)
+        let (fajr_offset, dhuhr_offset, asr_offset, maghrib_offset, isha_offset) = self.offsets;
+        let tune = |time: f64, offset: i8| time + offset as f64 / 60.0;
+
+        Some(Timings {
+            imsak: to_time(imsak),
+            fajr: to_time(tune(fajr, fajr_offset)),
+            sunrise: to_time(sunrise),
+            dhuhr: to_time(tune(dhuhr, dhuhr_offset)),
+            asr: to_time(tune(asr, asr_offset)),
+            sunset: to_time(sunset),
+            maghrib: to_time(tune(maghrib, maghrib_offset)),
+            isha: to_time(tune(isha, isha_offset)),
+            midnight: to_time(midnight),
+            firstthird: to_time(firstthird),
+            lastthird: to_time(lastthird),
+        })
+    }
+
+    // time (hours) of solar noon
+    fn mid_day(&self, jdate: f64, time: f64) -> f64 {
+        let (_, equation) = sun_position(jdate + time);
+        fix_hour(12.0 - equation)
+    }
+
+    // time (hours) at which the sun reaches the given angle below the horizon;
+    // ccw = before noon
+    fn sun_angle_time(&self, jdate: f64, angle: f64, time: f64, ccw: bool) -> f64 {
+        let (declination, _) = sun_position(jdate + time);
+        let noon = self.mid_day(jdate, time);
+        let t = (((-dsin(angle) - dsin(declination) * dsin(self.latitude))
+            / (dcos(declination) * dcos(self.latitude)))
+        .acos()
+        .to_degrees())
+            / 15.0;
+        noon + if ccw { -t } else { t }
+    }
+
+    fn asr_time(&self, jdate: f64, time: f64) -> f64 {
+        let (declination, _) = sun_position(jdate + time);
+        let angle = -(1.0 / (self.school.asr_factor() + dtan((self.latitude - declination).abs())))
+            .atan()
+            .to_degrees();
+        self.sun_angle_time(jdate, angle, time, false)
+    }
+}
+
+fn julian_date(date: NaiveDate) -> f64 {
+    let (mut year, mut month) = (date.year() as f64, date.month() as f64);
+    let day = date.day() as f64;
+    if month <= 2.0 {
+        year -= 1.0;
+        month += 12.0;
+    }
+    let a = (year / 100.0).floor();
+    let b = 2.0 - a + (a / 4.0).floor();
+    (365.25 * (year + 4716.0)).floor() + (30.6001 * (month + 1.0)).floor() + day + b - 1524.5
+}
+
+// sun declination (degrees) and equation of time (hours) for julian date
+fn sun_position(jd: f64) -> (f64, f64) {
+    let d = jd - 2451545.0;
+    let g = fix_angle(357.529 + 0.98560028 * d);
+    let q = fix_angle(280.459 + 0.98564736 * d);
+    let l = fix_angle(q + 1.915 * dsin(g) + 0.020 * dsin(2.0 * g));
+    let e = 23.439 - 0.00000036 * d;
+
+    let right_ascension = fix_hour((dcos(e) * dsin(l)).atan2(dcos(l)).to_degrees() / 15.0);
+    let equation = q / 15.0 - right_ascension;
+    let declination = (dsin(e) * dsin(l)).asin().to_degrees();
+    (declination, equation)
+}
+
+fn adjust_high_latitude(time: f64, base: f64, angle: f64, night: f64, ccw: bool) -> f64 {
+    let portion = angle / 60.0 * night;
+    let diff = if ccw {
+        time_diff(time, base)
+    } else {
+        time_diff(base, time)
+    };
+    if time.is_nan() || diff > portion {
+        base + if ccw { -portion } else { portion }
+    } else {
+        time
+    }
+}
+
+fn time_diff(from: f64, to: f64) -> f64 {
+    fix_hour(to - from)
+}
+
+// hours -> time of day, rounded to the nearest minute
+fn to_time(hours: f64) -> NaiveTime {
+    let minutes = (fix_hour(hours + 0.5 / 60.0) * 60.0).floor() as u32;
+    NaiveTime::from_hms_opt(minutes / 60 % 24, minutes % 60, 0).unwrap()
+}
+
+fn fix_angle(a: f64) -> f64 {
+    a - 360.0 * (a / 360.0).floor()
+}
+
+fn fix_hour(a: f64) -> f64 {
+    a - 24.0 * (a / 24.0).floor()
+}
+
+fn dsin(d: f64) -> f64 {
+    d.to_radians().sin()
+}
+
+fn dcos(d: f64) -> f64 {
+    d.to_radians().cos()
+}
+
+fn dtan(d: f64) -> f64 {
+    d.to_radians().tan()
+}
+
+// "HH:MM" representation as used by the aladhan api
+pub fn format_time(time: NaiveTime) -> String {
+    format!("{:02}:{:02}", time.hour(), time.minute())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // compare against a saved aladhan "HH:MM" timing; aladhan rounding occasionally differs by a minute
+    fn assert_close(time: NaiveTime, expected: &str) {
+        let expected = NaiveTime::parse_from_str(expected, "%H:%M").unwrap();
+        let diff = (time - expected).num_minutes().abs();
+        assert!(
+            diff <= 1 || diff >= 24 * 60 - 1,
+            "{} != {}",
+            format_time(time),
+            format_time(expected)
+        );
+    }
+
+    #[test]
+    fn test_matches_aladhan_auckland() {
+        // saved response: calendarByCity?city=Auckland&country=NewZealand&method=3 (01-11-2022, NZDT)
+        let calculator = Calculator::new(-36.8484597, 174.7633315, 3).unwrap();
+        let date = NaiveDate::from_ymd_opt(2022, 11, 1).unwrap();
+        let timings = calculator.timings(date, 13 * 3600).unwrap();
+
+        assert_close(timings.imsak, "04:30");
+        assert_close(timings.fajr, "04:40");
+        assert_close(timings.sunrise, "06:16");
+        assert_close(timings.dhuhr, "13:05");
+        assert_close(timings.asr, "16:49");
+        assert_close(timings.sunset, "19:53");
+        assert_close(timings.maghrib, "19:53");
+        assert_close(timings.isha, "21:24");
+        assert_close(timings.midnight, "01:05");
+        assert_close(timings.firstthird, "23:21");
+        assert_close(timings.lastthird, "02:49");
+    }
+
+    #[test]
+    fn test_hanafi_asr_is_later() {
+        let mut calculator = Calculator::new(-36.8484597, 174.7633315, 3).unwrap();
+        let date = NaiveDate::from_ymd_opt(2022, 11, 1).unwrap();
+        let shafi = calculator.timings(date, 13 * 3600).unwrap();
+        calculator.school = School::Hanafi;
+        let hanafi = calculator.timings(date, 13 * 3600).unwrap();
+
+        assert!(hanafi.asr > shafi.asr);
+        assert_eq!(hanafi.fajr, shafi.fajr);
+    }
+
+    #[test]
+    fn test_fixed_minutes_isha_and_offsets() {
+        // umm al-qura: isha is 90 minutes after maghrib
+        let mut calculator = Calculator::new(21.4225, 39.8262, 4).unwrap();
+        let date = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+        let timings = calculator.timings(date, 3 * 3600).unwrap();
+        assert_eq!(
+            timings.isha - timings.maghrib,
+            chrono::Duration::minutes(90)
+        );
+
+        calculator.offsets = (2, 0, 0, 0, -1);
+        let tuned = calculator.timings(date, 3 * 3600).unwrap();
+        assert_eq!(tuned.fajr - timings.fajr, chrono::Duration::minutes(2));
+        assert_eq!(tuned.isha - timings.isha, chrono::Duration::minutes(-1));
+    }
+
+    #[test]
+    fn test_unknown_method() {
+        assert!(Calculator::new(0.0, 0.0, 6).is_none());
+        assert!(Method::from_id(3).is_some());
+    }
+}
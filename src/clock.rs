@@ -0,0 +1,84 @@
+// Source of the current time and of waiting for the scheduler.
+//
+// `SystemClock` is the real thing; `FakeClock` is moved by hand and jumps ahead instead of sleeping,
+// so a schedule can be run through days (or months) instantly.
+use std::sync::Mutex;
+
+use crate::Command;
+
+pub trait Clock: Sync + Send {
+    fn now(&self) -> chrono::DateTime<chrono::Utc>;
+
+    // wait for duration unless a command arrives first; returns the command if woken
+    fn wait(
+        &self,
+        commands: &crossbeam_channel::Receiver<Command>,
+        duration: chrono::Duration,
+    ) -> Option<Command>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> chrono::DateTime<chrono::Utc> {
+        chrono::Utc::now()
+    }
+
+    fn wait(
+        &self,
+        commands: &crossbeam_channel::Receiver<Command>,
+        duration: chrono::Duration,
+    ) -> Option<Command> {
+        let duration = duration.to_std().unwrap_or_default();
+        match commands.recv_timeout(duration) {
+            Ok(command) => Some(command),
+            Err(crossbeam_channel::RecvTimeoutError::Timeout) => None,
+            Err(crossbeam_channel::RecvTimeoutError::Disconnected) => {
+                std::thread::sleep(duration);
+                None
+            }
+        }
+    }
+}
+
+// clock that only moves when told to, or by waiting
+pub struct FakeClock {
+    now: Mutex<chrono::DateTime<chrono::Utc>>,
+}
+
+impl FakeClock {
+    pub fn new(now: chrono::DateTime<chrono::Utc>) -> Self {
+        Self {
+            now: Mutex::new(now),
+        }
+    }
+
+    pub fn set(&self, now: chrono::DateTime<chrono::Utc>) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, duration: chrono::Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> chrono::DateTime<chrono::Utc> {
+        *self.now.lock().unwrap()
+    }
+
+    // pending commands are returned right away, otherwise the wait is skipped
+    fn wait(
+        &self,
+        commands: &crossbeam_channel::Receiver<Command>,
+        duration: chrono::Duration,
+    ) -> Option<Command> {
+        match commands.try_recv() {
+            Ok(command) => Some(command),
+            Err(_) => {
+                self.advance(duration.max(chrono::Duration::zero()));
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,230 @@
+// Daemon configuration, read from a TOML file (see `config.example.toml`) and overridden by
+// command line flags.
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::audio::{AudioBackend, BackendKind, NullBackend, RodioBackend};
+use crate::calc::School;
+use crate::provider::{AladhanProvider, LocalProvider, PrayerTimeProvider, TimetableProvider};
+use crate::structs::Params;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub listen: std::net::SocketAddr,
+    // directory for persisted timings and settings; kept in memory only if unset
+    pub data_dir: Option<PathBuf>,
+    pub log_level: String,
+    pub provider: ProviderKind,
+    // timetable file for the `timetable` provider
+    pub timetable: Option<PathBuf>,
+    // default location and method; fields changed at runtime via `/config` are saved and take
+    // precedence over these, the others follow this file (and the command line)
+    #[serde(deserialize_with = "location")]
+    pub location: Params,
+    pub audio: AudioConfig,
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    #[default]
+    Aladhan,
+    Local,
+    Timetable,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    // `null` plays nothing e.g. on a headless server
+    pub backend: BackendKind,
+    // default sink volume of playback
+    pub volume: f32,
+    // output device name; system default if unset
+    pub device: Option<String>,
+    // embedded assets played for fajr, the other adhans and chimes
+    pub fajr: String,
+    pub adhan: String,
+    pub chime: String,
+}
+
+// `[location]` table; keys left out keep their default (see `Params::default`)
+#[derive(Deserialize)]
+struct LocationConfig {
+    city: Option<String>,
+    country: Option<String>,
+    coordinates: Option<(f64, f64)>,
+    method: Option<u8>,
+    school: Option<School>,
+    offsets: Option<(i8, i8, i8, i8, i8)>,
+    hijri_adjustment: Option<i8>,
+    timezone: Option<chrono_tz::Tz>,
+}
+
+fn location<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Params, D::Error> {
+    let location = LocationConfig::deserialize(deserializer)?;
+    let mut params = Params::default();
+    // the default coordinates would take precedence over a configured city
+    if location.city.is_some() || location.country.is_some() {
+        params.coordinates = None;
+    }
+    if let Some(city) = location.city {
+        params.city = city;
+    }
+    if let Some(country) = location.country {
+        params.country = country;
+    }
+    params.coordinates = location.coordinates.or(params.coordinates);
+    params.method = location.method.unwrap_or(params.method);
+    params.school = location.school.unwrap_or(params.school);
+    params.offsets = location.offsets.unwrap_or(params.offsets);
+    params.hijri_adjustment = location.hijri_adjustment.unwrap_or(params.hijri_adjustment);
+    params.timezone = location.timezone.or(params.timezone);
+    Ok(params)
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            listen: std::net::SocketAddr::from(([0, 0, 0, 0], 3000)),
+            data_dir: None,
+            log_level: "info".to_owned(),
+            provider: ProviderKind::default(),
+            timetable: None,
+            location: Params::default(),
+            audio: AudioConfig::default(),
+        }
+    }
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            backend: BackendKind::default(),
+            volume: 5.0,
+            device: None,
+            fajr: "adhan-fajr.mp3".to_owned(),
+            adhan: "adhan-turkish.mp3".to_owned(),
+            chime: "sample.mp3".to_owned(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Error reading config file {:?}: {:?}", path, e))?;
+        toml::from_str(&contents)
+            .map_err(|e| format!("Error parsing config file {:?}: {}", path, e))
+    }
+
+    pub fn provider(&self) -> Box<dyn PrayerTimeProvider> {
+        match self.provider {
+            ProviderKind::Aladhan => Box::new(AladhanProvider),
+            ProviderKind::Local => Box::new(LocalProvider),
+            ProviderKind::Timetable => Box::new(TimetableProvider {
+                path: self
+                    .timetable
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from("timetable.csv")),
+            }),
+        }
+    }
+
+    pub fn audio_backend(&self) -> Box<dyn AudioBackend> {
+        match self.audio.backend {
+            BackendKind::Rodio => Box::new(RodioBackend {
+                device: self.audio.device.clone(),
+            }),
+            BackendKind::Null => Box::new(NullBackend),
+        }
+    }
+
+    // most verbose level logged, parsed from `log_level` e.g. "debug"
+    pub fn max_log_level(&self) -> Result<tracing::Level, String> {
+        self.log_level.parse().map_err(|_| {
+            format!(
+                "Invalid log level {:?}: expected trace, debug, info, warn or error",
+                self.log_level
+            )
+        })
+    }
+
+    // file in the data directory, if one is configured
+    pub fn data_file(&self, name: &str) -> Option<PathBuf> {
+        self.data_dir.as_ref().map(|dir| dir.join(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config: Config = toml::from_str(
+            r#"
+            listen = "127.0.0.1:8080"
+            data_dir = "/data"
+            provider = "local"
+
+            [location]
+            city = "London"
+            country = "UK"
+            coordinates = [51.5074, -0.1278]
+            method = 2
+            school = "Hanafi"
+
+            [audio]
+            backend = "null"
+            volume = 3.0
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.listen.port(), 8080);
+        assert_eq!(config.provider, ProviderKind::Local);
+        assert_eq!(config.location.city, "London");
+        assert_eq!(config.location.offsets, (0, 0, 0, 0, 0));
+        assert_eq!(config.audio.backend, BackendKind::Null);
+        assert_eq!(config.audio.volume, 3.0);
+        assert_eq!(config.audio.fajr, "adhan-fajr.mp3");
+        assert_eq!(
+            config.data_file("settings.json"),
+            Some(PathBuf::from("/data/settings.json"))
+        );
+        assert_eq!(config.log_level, "info");
+        assert_eq!(config.max_log_level(), Ok(tracing::Level::INFO));
+
+        let config: Config = toml::from_str(r#"log_level = "loud""#).unwrap();
+        assert!(config.max_log_level().is_err());
+    }
+
+    #[test]
+    fn test_partial_location() {
+        let config: Config = toml::from_str("[location]\nmethod = 2").unwrap();
+        assert_eq!(config.location.method, 2);
+        assert_eq!(
+            config.location,
+            Params {
+                method: 2,
+                ..Params::default()
+            }
+        );
+
+        // a city replaces the default coordinates
+        let config: Config = toml::from_str(
+            r#"
+            [location]
+            city = "London"
+            country = "UK"
+            timezone = "Europe/London"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.location.coordinates, None);
+        assert_eq!(config.location.method, 3);
+        assert_eq!(config.location.timezone, Some(chrono_tz::Europe::London));
+    }
+}
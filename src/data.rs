@@ -7,12 +7,16 @@ use std::sync::Mutex;
 // Use BtreeMap for storing data as key-value pairs, sorted by key
 use std::collections::BTreeMap;
 
+use serde::{de::DeserializeOwned, Serialize};
+use std::path::{Path, PathBuf};
+
 pub trait Database<V>: Sync + Send {
     type Key;
     fn get_all(&self) -> Vec<V>;
     fn get(&self, key: &Self::Key) -> Option<V>;
-    fn set_all(&self, keys: &Vec<Self::Key>, values: &Vec<V>);
+    fn set_all(&self, keys: &[Self::Key], values: &[V]);
     fn set(&self, key: &Self::Key, value: &V);
+    fn delete(&self, key: &Self::Key);
 }
 
 // Create a data store as a global variable with `Lazy` and `Mutex`.
@@ -42,6 +46,12 @@ impl<V> DataStore<V> {
     }
 }
 
+impl<V> Default for DataStore<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 unsafe impl<V> Sync for DataStore<V> {}
 unsafe impl<V> Send for DataStore<V> {}
 
@@ -63,15 +73,131 @@ where
         data.get(key).cloned()
     }
 
-    fn set_all(&self, keys: &Vec<Self::Key>, values: &Vec<V>) {
+    fn set_all(&self, keys: &[Self::Key], values: &[V]) {
+        let mut data = self.data.lock().unwrap();
+        for (key, value) in keys.iter().zip(values.iter()) {
+            data.insert(key.to_owned(), value.to_owned());
+        }
+    }
+
+    fn set(&self, key: &Self::Key, value: &V) {
+        let mut data = self.data.lock().unwrap();
+        data.insert(key.to_owned(), value.to_owned());
+    }
+
+    fn delete(&self, key: &Self::Key) {
+        let mut data = self.data.lock().unwrap();
+        data.remove(key);
+    }
+}
+
+// Persistent data store backed by a JSON file.
+//
+// The whole map is kept in memory and written out on every change (to a temporary file which is
+// then renamed over the original), so the file is never left half written if the Pi loses power.
+pub struct FileStore<V> {
+    path: PathBuf,
+    data: Mutex<BTreeMap<String, V>>,
+}
+
+impl<V> FileStore<V>
+where
+    V: Serialize + DeserializeOwned,
+{
+    // open store at path; a missing file is treated as an empty store
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        let data = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| format!("Error parsing data file {:?}: {:?}", path, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(format!("Error reading data file {:?}: {:?}", path, e)),
+        };
+        Ok(Self {
+            path,
+            data: Mutex::new(data),
+        })
+    }
+
+    fn persist(&self, data: &BTreeMap<String, V>) {
+        if let Err(e) = write_json(&self.path, data) {
+            tracing::error!("Error writing data file {:?}: {:?}", self.path, e);
+        }
+    }
+}
+
+// write value as json to a temporary file, then rename it over path
+pub(crate) fn write_json(path: &Path, value: &impl Serialize) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, serde_json::to_vec_pretty(value)?)?;
+    std::fs::rename(&tmp_path, path)
+}
+
+impl<V> Database<V> for FileStore<V>
+where
+    V: std::clone::Clone + std::fmt::Display,
+    V: std::fmt::Debug,
+    V: Serialize + DeserializeOwned + Send,
+{
+    type Key = String;
+
+    fn get_all(&self) -> Vec<V> {
+        let data = self.data.lock().unwrap();
+        data.values().cloned().collect()
+    }
+
+    fn get(&self, key: &Self::Key) -> Option<V> {
+        let data = self.data.lock().unwrap();
+        data.get(key).cloned()
+    }
+
+    fn set_all(&self, keys: &[Self::Key], values: &[V]) {
         let mut data = self.data.lock().unwrap();
         for (key, value) in keys.iter().zip(values.iter()) {
             data.insert(key.to_owned(), value.to_owned());
         }
+        self.persist(&data);
     }
 
     fn set(&self, key: &Self::Key, value: &V) {
         let mut data = self.data.lock().unwrap();
         data.insert(key.to_owned(), value.to_owned());
+        self.persist(&data);
+    }
+
+    fn delete(&self, key: &Self::Key) {
+        let mut data = self.data.lock().unwrap();
+        if data.remove(key).is_some() {
+            self.persist(&data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_store_survives_reopen() {
+        let path =
+            std::env::temp_dir().join(format!("prayer-alarm-test-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let store = FileStore::<String>::open(&path).unwrap();
+        store.set_all(
+            &["2022-12-30".to_owned(), "2022-12-31".to_owned()],
+            &["a".to_owned(), "b".to_owned()],
+        );
+        store.set(&"2022-12-31".to_owned(), &"c".to_owned());
+        store.delete(&"2022-12-30".to_owned());
+
+        let reopened = FileStore::<String>::open(&path).unwrap();
+        assert_eq!(reopened.get_all(), vec!["c".to_owned()]);
+        assert_eq!(reopened.get(&"2022-12-31".to_owned()), Some("c".to_owned()));
+
+        std::fs::remove_file(&path).unwrap();
     }
 }
@@ -0,0 +1,65 @@
+// Errors of fetching and parsing prayer timings.
+use serde::Serialize;
+
+use crate::structs::Prayer;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AdhanError {
+    #[error("error calling prayer times api: {0}")]
+    Network(#[from] reqwest::Error),
+    #[error("prayer times api returned {0}")]
+    Status(reqwest::StatusCode),
+    #[error("error decoding prayer times: {0}")]
+    Decode(#[from] serde_json::Error),
+    #[error("invalid {field} {value:?}")]
+    Parse { field: &'static str, value: String },
+    #[error("missing {0:?} timing")]
+    MissingTiming(Prayer),
+    // anything else a provider fails with e.g. an unreadable timetable
+    #[error("{0}")]
+    Provider(String),
+}
+
+impl AdhanError {
+    pub fn parse(field: &'static str, value: impl Into<String>) -> Self {
+        AdhanError::Parse {
+            field,
+            value: value.into(),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AdhanError::Network(_) => "network",
+            AdhanError::Status(_) => "http_status",
+            AdhanError::Decode(_) => "decode",
+            AdhanError::Parse { .. } => "parse",
+            AdhanError::MissingTiming(_) => "missing_timing",
+            AdhanError::Provider(_) => "provider",
+        }
+    }
+}
+
+impl From<String> for AdhanError {
+    fn from(message: String) -> Self {
+        AdhanError::Provider(message)
+    }
+}
+
+// last error of the scheduler, as reported by `/status`
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ErrorReport {
+    pub kind: &'static str,
+    pub message: String,
+    pub datetime: chrono::DateTime<chrono::Utc>,
+}
+
+impl ErrorReport {
+    pub fn new(error: &AdhanError, datetime: chrono::DateTime<chrono::Utc>) -> Self {
+        Self {
+            kind: error.kind(),
+            message: error.to_string(),
+            datetime,
+        }
+    }
+}
@@ -0,0 +1,134 @@
+// Offline Hijri calendar, for providers without one (local calculation, timetables).
+//
+// Uses the tabular (arithmetical) Islamic calendar; the start of a month by moon sighting may differ
+// from it by a day or two, hence the adjustment in days.
+use chrono::Datelike;
+use serde::Serialize;
+
+use crate::structs::HijriDate;
+
+// julian day number of 1 Muharram 1 AH (civil epoch)
+const EPOCH: i64 = 1948440;
+
+// julian day number of 0001-01-01 minus one (`num_days_from_ce` of 0001-01-01 is 1)
+const CE_OFFSET: i64 = 1721425;
+
+// same transliteration as the aladhan api
+pub const MONTHS: [&str; 12] = [
+    "Muḥarram",
+    "Ṣafar",
+    "Rabīʿ al-awwal",
+    "Rabīʿ al-thānī",
+    "Jumādá al-ūlá",
+    "Jumādá al-ākhirah",
+    "Rajab",
+    "Shaʿbān",
+    "Ramaḍān",
+    "Shawwāl",
+    "Dhū al-Qaʿdah",
+    "Dhū al-Ḥijjah",
+];
+
+// hijri date of gregorian date, shifted by `adjustment` days
+pub fn from_gregorian(date: chrono::NaiveDate, adjustment: i8) -> HijriDate {
+    let jd = date.num_days_from_ce() as i64 + CE_OFFSET + adjustment as i64;
+
+    let l = jd - EPOCH + 10632;
+    let n = (l - 1) / 10631;
+    let l = l - 10631 * n + 354;
+    let j = ((10985 - l) / 5316) * ((50 * l) / 17719) + (l / 5670) * ((43 * l) / 15238);
+    let l = l - ((30 - j) / 15) * ((17719 * j) / 50) - (j / 16) * ((15238 * j) / 43) + 29;
+    let month = (24 * l) / 709;
+    let day = l - (709 * month) / 24;
+    let year = 30 * n + j - 30;
+
+    HijriDate {
+        day: day as u8,
+        month: month as u8,
+        year: year as u16,
+        month_name: MONTHS[month as usize - 1].to_owned(),
+    }
+}
+
+// islamic holidays falling on hijri date (names as in the aladhan api)
+pub fn holidays(hijri: &HijriDate) -> Vec<String> {
+    let holiday = match (hijri.month, hijri.day) {
+        (1, 1) => "Islamic New Year",
+        (1, 10) => "Ashura",
+        (3, 12) => "Mawlid al-Nabi",
+        (7, 27) => "Lailat-ul-Miraj",
+        (8, 15) => "Lailat-ul-Bara'at",
+        (9, 1) => "1st Day of Ramadan",
+        (9, 27) => "Lailat-ul-Qadr",
+        (10, 1) => "Eid-ul-Fitr",
+        (12, 9) => "Arafa",
+        (12, 10) => "Eid-ul-Adha",
+        _ => return vec![],
+    };
+    vec![holiday.to_owned()]
+}
+
+// day of the combined gregorian/hijri month view
+#[derive(Debug, Clone, Serialize)]
+pub struct CalendarDay {
+    pub date: String,
+    pub weekday: String,
+    pub hijri: HijriDate,
+    pub holidays: Vec<String>,
+}
+
+impl CalendarDay {
+    pub fn new(date: chrono::NaiveDate, hijri: HijriDate, holidays: Vec<String>) -> Self {
+        Self {
+            date: date.to_string(),
+            weekday: date.format("%A").to_string(),
+            hijri,
+            holidays,
+        }
+    }
+}
+
+// every day of the month of `date`, using the offline calendar
+pub fn month(date: chrono::NaiveDate, adjustment: i8) -> Vec<CalendarDay> {
+    let first = date.with_day(1).unwrap();
+    first
+        .iter_days()
+        .take_while(|day| day.month() == first.month())
+        .map(|day| {
+            let hijri = from_gregorian(day, adjustment);
+            let holidays = holidays(&hijri);
+            CalendarDay::new(day, hijri, holidays)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_gregorian() {
+        let date = chrono::NaiveDate::from_ymd_opt(2022, 11, 1).unwrap();
+        let hijri = from_gregorian(date, 0);
+        assert_eq!((hijri.day, hijri.month, hijri.year), (6, 4, 1444));
+        assert_eq!(hijri.month_name, "Rabīʿ al-thānī");
+
+        // 1 ramadan 1444, one day later with a +1 adjustment
+        let date = chrono::NaiveDate::from_ymd_opt(2023, 3, 23).unwrap();
+        let hijri = from_gregorian(date, 0);
+        assert_eq!((hijri.day, hijri.month, hijri.year), (1, 9, 1444));
+        assert_eq!(holidays(&hijri), vec!["1st Day of Ramadan"]);
+        let hijri = from_gregorian(date, 1);
+        assert_eq!((hijri.day, hijri.month), (2, 9));
+
+        // year boundary: 1 muharram 1445
+        let date = chrono::NaiveDate::from_ymd_opt(2023, 7, 19).unwrap();
+        let hijri = from_gregorian(date, 0);
+        assert_eq!((hijri.day, hijri.month, hijri.year), (1, 1, 1445));
+
+        let days = month(date, 0);
+        assert_eq!(days.len(), 31);
+        assert_eq!(days[18].holidays, vec!["Islamic New Year"]);
+        assert_eq!(days[18].weekday, "Wednesday");
+    }
+}
@@ -1,225 +1,1439 @@
 use chrono::Datelike;
-use reqwest;
-use rodio::{Decoder, OutputStream, Sink};
-use std::io::BufReader;
 use std::sync::Arc;
 
+pub mod audio;
+use audio::{AudioBackend, Playback, Track};
+
+pub mod calc;
+
+pub mod clock;
+use clock::Clock;
+
+pub mod hijri;
+
+pub mod config;
+use config::AudioConfig;
+
+pub mod error;
+use error::{AdhanError, ErrorReport};
+
 pub mod structs;
-use structs::{Params, Prayer, PrayerTime};
+use structs::{Params, Prayer, PrayerTime, RamadanEvent};
 
 pub mod data;
 use data::Database;
 
-#[derive(Debug)]
+pub mod provider;
+use provider::{LocalProvider, MonthCache, PrayerTimeProvider};
+
+pub mod rules;
+use rules::EventKind;
+
+pub mod settings;
+use settings::{Settings, SettingsStore};
+
+pub mod sounds;
+use sounds::SoundLibrary;
+
+pub mod timezone;
+use timezone::Zone;
+
+pub mod status;
+use status::{NextEvent, Notification, PlayerStatus, Status};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Signal {
     Play,
+    // friday prayer, in place of (or as well as) the dhuhr adhan
+    Jumuah,
+    Chime(Chime),
     Stop,
+    // hold the running adhan at its position, until resumed
+    Pause,
+    Resume,
     VolumeUp,
     VolumeDown,
+    // absolute volume, percent
+    SetVolume(u8),
 }
 
-#[derive(rust_embed::RustEmbed)]
-#[folder = "mp3/"]
-struct Assets;
+// signals sent to the player in one message; events due at the same time (e.g. iftar and the
+// maghrib adhan) are played one after another
+pub type Signals = Vec<(Signal, Prayer)>;
 
-pub struct AdhanService<'a> {
-    pub params: Params<'a>,
-    pub sender: crossbeam_channel::Sender<(Signal, Prayer)>,
-    pub database: Arc<dyn Database<PrayerTime, Key = String>>,
+// short alerts played instead of the adhan
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Chime {
+    Iqamah,
+    // minutes before the adhan
+    Reminder,
+    Ramadan(RamadanEvent),
+    LeaveForJumuah,
+    // in place of an adhan during quiet hours
+    QuietHours,
 }
 
-impl<'a> AdhanService<'a> {
-    // TODO: return custom errors (api call & response deserialization/parsing)
-    fn get_prayer_timings(&self) -> Result<Vec<PrayerTime>, String> {
-        let api_url = self.params.to_prayer_timings_url();
+impl Signal {
+    // signals that start playback
+    fn is_playable(&self) -> bool {
+        matches!(self, Signal::Play | Signal::Jumuah | Signal::Chime(_))
+    }
 
-        let monthly_prayer_timings: structs::api::PrayerCalendarResponse =
-            match reqwest::blocking::get(api_url) {
-                Ok(response) => match response.json() {
-                    Ok(json) => json,
-                    Err(e) => return Err(format!("Error parsing response: {:?}", e)),
-                },
-                Err(e) => return Err(format!("Error calling API: {:?}", e)),
-            };
-        let current_date_time = chrono::Local::now().naive_local();
+    // kind of scheduled event, for mute rules
+    fn event_kind(&self) -> Option<EventKind> {
+        match self {
+            Signal::Play => Some(EventKind::Adhan),
+            Signal::Jumuah | Signal::Chime(Chime::LeaveForJumuah) => Some(EventKind::Jumuah),
+            Signal::Chime(Chime::Iqamah) => Some(EventKind::Iqamah),
+            Signal::Chime(Chime::Reminder) => Some(EventKind::Reminder),
+            Signal::Chime(Chime::Ramadan(_)) => Some(EventKind::Ramadan),
+            _ => None,
+        }
+    }
+}
+
+// control messages for the running prayer alarm scheduler
+#[derive(Debug)]
+pub enum Command {
+    // fetch stored timings again right away, skipping any pending retry; toggles are kept
+    Reset,
+    // settings changed; recompute derived timings (e.g. iqamah) and reschedule
+    Refresh,
+    // location/method changed; fetch stored timings again, keeping toggles
+    Reload,
+}
+
+// name of the event a signal is sent for e.g. "adhan", "iqamah"
+pub fn event_name(signal: Signal, prayer: Prayer) -> String {
+    match signal {
+        Signal::Chime(Chime::Iqamah) => "iqamah".to_owned(),
+        Signal::Chime(Chime::Reminder) => "reminder".to_owned(),
+        Signal::Chime(Chime::LeaveForJumuah) => "leave for jumu'ah".to_owned(),
+        Signal::Chime(Chime::QuietHours) => "chime".to_owned(),
+        Signal::Jumuah => "jumu'ah".to_owned(),
+        Signal::Chime(Chime::Ramadan(event)) => format!("{:?}", event).to_lowercase(),
+        _ if !prayer.is_prayer() => "alarm".to_owned(),
+        _ => "adhan".to_owned(),
+    }
+}
+
+// signal to send for a prayer on a date ("YYYY-MM-DD")
+type Event = (Signal, Prayer, String);
+
+// (re)compute the timings derived from the adhan timings and settings
+fn apply_settings(prayer_time: &mut PrayerTime, settings: &Settings) {
+    prayer_time.set_iqamah(&settings.iqamah.schedules);
+    prayer_time.set_reminders(&settings.reminders.minutes);
+    prayer_time.set_ramadan(&settings.ramadan);
+}
+
+// events of a day in the order they are played when due at the same time; on fridays jumu'ah is
+// added (and dhuhr, with its reminder and iqamah, muted if configured or if jumu'ah replaces it at
+// the same time); the imsak alarm is only played during ramadan
+fn day_events(
+    p: &PrayerTime,
+    date: chrono::NaiveDate,
+    settings: &Settings,
+) -> Vec<(chrono::NaiveTime, Signal, Prayer)> {
+    let parse = |time: &String| chrono::NaiveTime::parse_from_str(time, "%H:%M:%S").ok();
+    let jumuah = p
+        .prayer_time(Prayer::Dhuhr)
+        .and_then(|dhuhr| settings.jumuah.times(date, dhuhr));
+    let replaced = |prayer: &Prayer| {
+        *prayer == Prayer::Dhuhr
+            && jumuah.is_some_and(|(jumuah, _)| {
+                settings.jumuah.mute_dhuhr || p.prayer_time(Prayer::Dhuhr) == Some(jumuah)
+            })
+    };
+
+    let reminders = p
+        .reminders
+        .iter()
+        .filter(|(_, prayer)| !replaced(prayer))
+        .map(|(time, prayer)| (parse(time), Signal::Chime(Chime::Reminder), *prayer));
+    let leave = jumuah.and_then(|(_, leave)| leave).map(|time| {
+        (
+            Some(time),
+            Signal::Chime(Chime::LeaveForJumuah),
+            Prayer::Dhuhr,
+        )
+    });
+    let ramadan = p.ramadan.iter().map(|(time, event)| {
+        (
+            parse(time),
+            Signal::Chime(Chime::Ramadan(*event)),
+            event.prayer(),
+        )
+    });
+    let adhans = p
+        .timings
+        .iter()
+        .filter(|(_, prayer)| !replaced(prayer))
+        .filter(|(_, prayer)| **prayer != Prayer::Imsak || p.is_ramadan(&settings.ramadan))
+        .map(|(time, prayer)| (parse(time), Signal::Play, *prayer));
+    let jumuah = jumuah.map(|(time, _)| (Some(time), Signal::Jumuah, Prayer::Dhuhr));
+    let iqamahs = p
+        .iqamah
+        .iter()
+        .filter(|(_, prayer)| !replaced(prayer))
+        .map(|(time, prayer)| (parse(time), Signal::Chime(Chime::Iqamah), *prayer));
+
+    reminders
+        .chain(leave)
+        .chain(ramadan)
+        .chain(adhans)
+        .chain(jumuah)
+        .chain(iqamahs)
+        .filter_map(|(time, signal, prayer)| Some((time?, signal, prayer)))
+        .collect()
+}
+
+// fetch the next month this many days before the current one ends
+const PREFETCH_DAYS: i64 = 7;
+
+// first wait before retrying a failed fetch; doubled on every failure up to MAX_RETRY_INTERVAL
+const RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+const MAX_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30 * 60);
+
+// longest wait before looking at the clock again, so that the schedule follows changes of the system
+// clock
+const MAX_WAIT: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+// exponential backoff of retries of failed fetches
+#[derive(Debug, Default)]
+pub struct Backoff {
+    // consecutive failures and when to try again
+    state: std::sync::Mutex<(u32, Option<chrono::DateTime<chrono::Utc>>)>,
+}
+
+impl Backoff {
+    // record a failure at now; returns the time of the next attempt
+    fn failed(&self, now: chrono::DateTime<chrono::Utc>) -> chrono::DateTime<chrono::Utc> {
+        let mut state = self.state.lock().unwrap();
+        let delay = RETRY_INTERVAL
+            .saturating_mul(2u32.saturating_pow(state.0))
+            .min(MAX_RETRY_INTERVAL);
+        let retry_at = now + chrono::Duration::from_std(delay).unwrap();
+        *state = (state.0 + 1, Some(retry_at));
+        retry_at
+    }
+
+    fn succeeded(&self) {
+        *self.state.lock().unwrap() = (0, None);
+    }
+
+    fn retry_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.state.lock().unwrap().1
+    }
+}
+
+pub struct AdhanService {
+    pub sender: crossbeam_channel::Sender<Signals>,
+    pub database: Arc<dyn Database<PrayerTime, Key = String>>,
+    pub provider: Box<dyn PrayerTimeProvider>,
+    pub commands: crossbeam_channel::Receiver<Command>,
+    pub settings: Arc<SettingsStore>,
+    pub status: Arc<Status>,
+    pub clock: Arc<dyn Clock>,
+    // fallback when the provider fails
+    pub cache: MonthCache,
+    pub retry: Backoff,
+}
+
+impl AdhanService {
+    // prayer timings for the month of `date`, excluding days that have already passed
+    fn get_prayer_timings(&self, date: chrono::NaiveDate) -> Result<Vec<PrayerTime>, AdhanError> {
+        // every provider calculates in the same zone (the system's if the location's is unknown)
+        let zone = self.zone();
+        let params = Params {
+            date,
+            timezone: match zone {
+                Zone::Iana(tz) => Some(tz),
+                Zone::System => None,
+            },
+            ..self.settings.get().params
+        };
+        let monthly_prayer_timings = self.fetch_prayer_timings(&params)?;
+        let today = zone.local(self.clock.now()).date();
 
         let prayer_timings: Vec<PrayerTime> = monthly_prayer_timings
-            .data
-            .iter()
-            .filter_map(
-                // parse gregorian date using DD-MM-YYYY format
-                |data| match chrono::NaiveDate::parse_from_str(
-                    &data.date.gregorian.date,
-                    "%d-%m-%Y",
-                ) {
-                    Ok(date) => {
-                        // check if day of date is after current day
-                        if date.day() >= current_date_time.day() {
-                            let mut prayer_time: PrayerTime = data.to_owned().into(); // api response -> PrayerTime
-
-                            // only retain prayer times yet to come
-                            prayer_time.timings.retain(|timing_time, _| {
-                                let timing_time =
-                                    chrono::NaiveTime::parse_from_str(timing_time, "%H:%M:%S")
-                                        .unwrap();
-                                chrono::NaiveDateTime::new(date, timing_time) >= current_date_time
-                            });
-
-                            Some(prayer_time)
-                        } else {
-                            None
-                        }
-                    }
+            .into_iter()
+            .filter(|prayer_time| {
+                // parse date using YYYY-MM-DD format
+                match chrono::NaiveDate::parse_from_str(&prayer_time.date, "%Y-%m-%d") {
+                    Ok(date) => date >= today,
                     Err(e) => {
                         tracing::error!("Error parsing date: {:?}", e);
-                        None
+                        false
                     }
-                },
-            )
+                }
+            })
             .collect();
 
         Ok(prayer_timings)
     }
 
-    pub fn init_prayer_alarm(&self) {
-        tracing::info!("current time: {:#}", chrono::Local::now().naive_local());
+    // fetch and store timings for the month of `date`; toggles of already stored days are kept
+    fn load_prayer_timings(&self, date: chrono::NaiveDate) -> Result<(), AdhanError> {
+        let settings = self.settings.get();
+        let prayer_times: Vec<PrayerTime> = self
+            .get_prayer_timings(date)?
+            .into_iter()
+            .map(|prayer_time| match self.database.get(&prayer_time.date) {
+                Some(stored) => PrayerTime {
+                    play_adhan: stored.play_adhan,
+                    play_iqamah: stored.play_iqamah,
+                    play_reminder: stored.play_reminder,
+                    play_ramadan: stored.play_ramadan,
+                    ..prayer_time
+                },
+                None => prayer_time,
+            })
+            .map(|mut prayer_time| {
+                apply_settings(&mut prayer_time, &settings);
+                prayer_time
+            })
+            .collect();
+        tracing::info!(
+            "loaded {} days of prayer timings for {}",
+            prayer_times.len(),
+            date.format("%Y-%m")
+        );
 
-        let prayer_times = self
-            .get_prayer_timings()
-            .expect("error getting prayer times");
         let prayer_keys = prayer_times
             .iter()
             .map(|prayer_time| prayer_time.date.to_owned())
             .collect::<Vec<String>>();
         self.database.set_all(&prayer_keys, &prayer_times);
+        Ok(())
+    }
+
+    // zone of the location: configured, else the one of the last month fetched from the provider
+    // (the api's `meta.timezone`), else the system's
+    fn zone(&self) -> Zone {
+        self.settings
+            .get()
+            .params
+            .timezone
+            .or_else(|| self.cache.timezone())
+            .map(Zone::Iana)
+            .unwrap_or_else(Zone::system)
+    }
+
+    // timings from the provider; if it fails, from the cached month or the offline calculator
+    // (reported as degraded in `/status`) until a retry succeeds
+    fn fetch_prayer_timings(&self, params: &Params) -> Result<Vec<PrayerTime>, AdhanError> {
+        let error = match self.provider.monthly_timings(params) {
+            Ok(prayer_times) => {
+                self.cache.save(&prayer_times);
+                // still degraded (and retrying) while another month is from a fallback
+                self.status.set_fallback(params.date, None);
+                if self.status.fallback().is_none() {
+                    self.retry.succeeded();
+                    self.status.set_error(None);
+                }
+                return Ok(prayer_times);
+            }
+            Err(e) => e,
+        };
+
+        let retry_at = self.retry.failed(self.clock.now());
+        tracing::error!(
+            "error getting prayer times: {}; retrying at {}",
+            error,
+            retry_at
+        );
+        self.status
+            .set_error(Some(ErrorReport::new(&error, self.clock.now())));
 
-        for p in prayer_times {
-            // construct datetime from date and prayer time
-            for (time, prayer) in p.timings.iter() {
-                let datetime = chrono::NaiveDateTime::new(
-                    chrono::NaiveDate::parse_from_str(&p.date, "%Y-%m-%d")
-                        .expect("error parsing date"),
-                    chrono::NaiveTime::parse_from_str(time, "%H:%M:%S")
-                        .expect("error parsing time"),
+        let fallback = match self.cache.exact(params) {
+            Some(prayer_times) => Some(("cache", prayer_times)),
+            None => match LocalProvider.monthly_timings(params) {
+                Ok(prayer_times) => Some(("calculator", prayer_times)),
+                Err(_) => self
+                    .cache
+                    .approximate(params)
+                    .map(|prayer_times| ("cache", prayer_times)),
+            },
+        };
+        match fallback {
+            Some((source, prayer_times)) => {
+                tracing::warn!(
+                    "using prayer times from the {} until the next retry",
+                    source
                 );
-                // loop through timings until you get to next prayer time (in future) - from current time
-                let naive_now = chrono::Local::now().naive_local();
-                if datetime > naive_now {
-                    // calculate time difference between current time and next prayer time
-                    let time_diff = datetime - naive_now;
-                    let (hours, mins) = (
-                        time_diff.num_seconds() / 3600,
-                        (time_diff.num_seconds() % 3600) / 60,
-                    );
-                    tracing::info!(
-                        "Time till {:?} adhan ({:?}) - {:?}:{:?}:00...",
-                        prayer,
-                        datetime,
-                        hours,
-                        mins,
-                    );
-                    // sleep for duration
-                    std::thread::sleep(time_diff.to_std().unwrap());
-                    // get play adhan status from db object; if set to true, play adhan
-                    if self
-                        .database
-                        .get(&p.date)
-                        .expect("error getting prayer time")
-                        .play_adhan
-                        .get(prayer)
-                        .expect("error getting play adhan status")
-                        .to_owned()
-                    {
-                        self.sender
-                            .send((Signal::Play, prayer.to_owned()))
-                            .expect("error sending signal to adhan player");
-                    }
+                self.status.set_fallback(params.date, Some(source));
+                Ok(prayer_times)
+            }
+            None => Err(error),
+        }
+    }
+
+    // next adhan/iqamah/reminder/ramadan/jumu'ah alarm (after `now`) from stored timings, with any
+    // other events due at the same time; alerts are ordered before the adhan. Compared as instants
+    // (each day in the zone it was calculated in), so an hour repeated when clocks go back does not
+    // bring back events that already fired
+    fn next_events(
+        &self,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Option<(chrono::DateTime<chrono::Utc>, Vec<Event>)> {
+        let settings = self.settings.get();
+        let events: Vec<(chrono::DateTime<chrono::Utc>, Signal, Prayer, String)> = self
+            .database
+            .get_all()
+            .iter()
+            .filter_map(|p| {
+                let date = chrono::NaiveDate::parse_from_str(&p.date, "%Y-%m-%d").ok()?;
+                let zone = p.timezone.map(Zone::Iana).unwrap_or_else(Zone::system);
+                let events = day_events(p, date, &settings).into_iter().map(
+                    move |(time, signal, prayer)| {
+                        let datetime = zone.instant(chrono::NaiveDateTime::new(date, time));
+                        (datetime, signal, prayer, p.date.to_owned())
+                    },
+                );
+                Some(events)
+            })
+            .flatten()
+            .filter(|(datetime, _, _, _)| *datetime > now)
+            .collect();
+
+        let next = events.iter().map(|(datetime, _, _, _)| *datetime).min()?;
+        let due = events
+            .into_iter()
+            .filter(|(datetime, _, _, _)| *datetime == next)
+            .map(|(_, signal, prayer, date)| (signal, prayer, date))
+            .collect();
+        Some((next, due))
+    }
+
+    // whether the event should be played according to the stored toggles and settings
+    fn is_enabled(&self, signal: Signal, prayer: Prayer, date: &str) -> bool {
+        let prayer_time = match self.database.get(&date.to_owned()) {
+            Some(prayer_time) => prayer_time,
+            None => return false,
+        };
+        let muted = match (signal.event_kind(), date.parse()) {
+            (Some(kind), Ok(date)) => {
+                rules::is_muted(&self.settings.get().mute_rules, kind, prayer, date)
+            }
+            _ => false,
+        };
+        if muted {
+            tracing::info!("{:?} {:?} muted by rule", prayer, signal);
+            return false;
+        }
+
+        match signal {
+            Signal::Play => prayer_time
+                .play_adhan
+                .get(&prayer)
+                .copied()
+                .unwrap_or(false),
+            Signal::Chime(Chime::Iqamah) => {
+                self.settings.get().iqamah.chime
+                    && prayer_time
+                        .play_iqamah
+                        .get(&prayer)
+                        .copied()
+                        .unwrap_or(false)
+            }
+            Signal::Chime(Chime::Reminder) => prayer_time
+                .play_reminder
+                .get(&prayer)
+                .copied()
+                .unwrap_or(false),
+            // follow the day's dhuhr toggles
+            Signal::Jumuah => prayer_time
+                .play_adhan
+                .get(&Prayer::Dhuhr)
+                .copied()
+                .unwrap_or(false),
+            Signal::Chime(Chime::LeaveForJumuah) => prayer_time
+                .play_reminder
+                .get(&Prayer::Dhuhr)
+                .copied()
+                .unwrap_or(false),
+            Signal::Chime(Chime::Ramadan(event)) => prayer_time
+                .play_ramadan
+                .get(&event)
+                .copied()
+                .unwrap_or(false),
+            _ => false,
+        }
+    }
+
+    // fetches every stored month again; `load_prayer_timings` keeps the play toggles of each day
+    fn reload_prayer_timings(&self) {
+        // one date per stored month
+        let mut months: Vec<chrono::NaiveDate> = self
+            .database
+            .get_all()
+            .iter()
+            .filter_map(|p| chrono::NaiveDate::parse_from_str(&p.date, "%Y-%m-%d").ok())
+            .filter_map(|date| date.with_day(1))
+            .collect();
+        months.dedup();
+        for month in months {
+            if let Err(e) = self.load_prayer_timings(month) {
+                tracing::error!("no prayer times for {}: {}", month.format("%Y-%m"), e);
+            }
+        }
+    }
+
+    fn handle_command(&self, command: Command) {
+        match command {
+            Command::Reset => {
+                tracing::warn!("resetting adhan timings...");
+                self.retry.succeeded();
+                self.reload_prayer_timings();
+            }
+            Command::Reload => {
+                tracing::info!("reloading adhan timings...");
+                self.reload_prayer_timings();
+            }
+            Command::Refresh => {
+                let settings = self.settings.get();
+                for mut prayer_time in self.database.get_all() {
+                    apply_settings(&mut prayer_time, &settings);
+                    self.database
+                        .set(&prayer_time.date.to_owned(), &prayer_time);
                 }
             }
         }
+        self.status.publish(Notification::TimingsRefreshed);
+    }
+
+    // runs forever: keeps the upcoming days (and the next month, ahead of time) loaded and plays the
+    // adhan of each prayer as its time comes
+    pub fn init_prayer_alarm(&self) {
+        loop {
+            self.tick();
+        }
+    }
 
-        // calculate time till next day
-        let current_time = chrono::Local::now().naive_local();
-        let tomorrow = chrono::NaiveDateTime::new(
-            current_time.date() + chrono::Duration::days(1),
-            chrono::NaiveTime::from_hms(0, 0, 0),
+    // one round of the scheduler: wait for the next events (or a command) and send them if due
+    fn tick(&self) {
+        // timings are wall clock times of the location
+        let zone = self.zone();
+        self.status.set_timezone(zone);
+        let instant = self.clock.now();
+        let now = zone.local(instant);
+        tracing::info!(
+            "current time: {:#} ({})",
+            now,
+            zone.name().unwrap_or_else(|| "system time".to_owned())
         );
-        let time_diff = tomorrow - current_time;
-        let time_diff_with_offset = time_diff
-            .checked_add(&chrono::Duration::minutes(5))
-            .unwrap();
 
-        // sleep for duration + 5mins offset (12:05 am) - until next calendar month
-        std::thread::sleep(time_diff_with_offset.to_std().unwrap());
+        // drop days that have passed
+        for prayer_time in self.database.get_all() {
+            if prayer_time.date < now.date().to_string() {
+                self.database.delete(&prayer_time.date);
+            }
+        }
+        self.status.clear_fallback_before(now.date());
+
+        // make sure today and the coming days are loaded - crossing into the next month early; after
+        // a failed fetch (or while falling back) they are fetched again once the retry is due
+        let mut months: Vec<chrono::NaiveDate> = [
+            now.date(),
+            now.date() + chrono::Duration::days(PREFETCH_DAYS),
+        ]
+        .into_iter()
+        .filter(|date| match self.retry.retry_at() {
+            Some(retry_at) => instant >= retry_at,
+            None => self.database.get(&date.to_string()).is_none(),
+        })
+        .collect();
+        months.dedup_by_key(|date| date.month());
+        for date in months {
+            match self.load_prayer_timings(date) {
+                Ok(_) => self.status.publish(Notification::TimingsRefreshed),
+                Err(e) => tracing::error!("no prayer times for {}: {}", date.format("%Y-%m"), e),
+            }
+        }
+
+        let (due, events) = match self.next_events(instant) {
+            Some(next_events) => next_events,
+            None => {
+                self.status.set_next(vec![]);
+                let retry_at = self
+                    .retry
+                    .retry_at()
+                    .unwrap_or(instant + chrono::Duration::from_std(RETRY_INTERVAL).unwrap());
+                tracing::warn!("no upcoming prayer times, retrying at {}...", retry_at);
+                if let Some(command) = self.clock.wait(&self.commands, retry_at - instant) {
+                    self.handle_command(command);
+                }
+                return;
+            }
+        };
+
+        // calculate time difference between current time and next prayer time
+        let time_diff = due - instant;
+        let (hours, mins) = (
+            time_diff.num_seconds() / 3600,
+            (time_diff.num_seconds() % 3600) / 60,
+        );
+        for (signal, prayer, _) in &events {
+            tracing::info!(
+                "Time till {:?} {} ({:?}) - {:?}:{:?}:00...",
+                prayer,
+                event_name(*signal, *prayer),
+                zone.local(due),
+                hours,
+                mins,
+            );
+        }
+        self.status.set_next(
+            events
+                .iter()
+                .map(|(signal, prayer, _)| NextEvent {
+                    datetime: zone.with_offset(due),
+                    prayer: *prayer,
+                    event: event_name(*signal, *prayer),
+                })
+                .collect(),
+        );
+
+        // sleep for duration; reschedule if woken up by a command, early or for a retry
+        let mut wait = time_diff.min(chrono::Duration::from_std(MAX_WAIT).unwrap());
+        if let Some(retry_at) = self.retry.retry_at() {
+            wait = wait.min(retry_at - instant);
+        }
+        if let Some(command) = self.clock.wait(&self.commands, wait) {
+            self.handle_command(command);
+            return;
+        }
+        if self.clock.now() < due {
+            return;
+        }
+
+        // get play status from db object; if set to true, play adhan/chime - events due together
+        // are sent together
+        let signals: Signals = events
+            .into_iter()
+            .filter(|(signal, prayer, date)| self.is_enabled(*signal, *prayer, date))
+            .map(|(signal, prayer, _)| (signal, prayer))
+            .collect();
+        if signals.is_empty() {
+            return;
+        }
+        for (signal, prayer) in &signals {
+            self.status.publish(Notification::PrayerFired {
+                prayer: *prayer,
+                event: event_name(*signal, *prayer),
+            });
+        }
+        self.sender
+            .send(signals)
+            .expect("error sending signal to adhan player");
+    }
+}
+
+// sound assigned to the signal in settings, falling back to the configured embedded asset
+fn resolve_sound(
+    signal: Signal,
+    prayer: Prayer,
+    audio: &AudioConfig,
+    sounds: &SoundLibrary,
+    settings: &SettingsStore,
+) -> Option<(String, Vec<u8>)> {
+    let assigned = settings.get().sounds;
+    let (assigned, fallback) = match (signal, prayer) {
+        (Signal::Play, Prayer::Fajr) => (assigned.prayers.get(&prayer).cloned(), &audio.fajr),
+        (Signal::Play, _) if prayer.is_prayer() => {
+            (assigned.prayers.get(&prayer).cloned(), &audio.adhan)
+        }
+        // sunrise, imsak etc. are alerts rather than an adhan
+        (Signal::Play, _) => (assigned.prayers.get(&prayer).cloned(), &audio.chime),
+        (Signal::Chime(Chime::Reminder), _) => (
+            assigned.reminders.get(&prayer).cloned().or(assigned.chime),
+            &audio.chime,
+        ),
+        (Signal::Chime(Chime::Ramadan(event)), _) => (
+            assigned.ramadan.get(&event).cloned().or(assigned.chime),
+            &audio.chime,
+        ),
+        (Signal::Jumuah, _) => (
+            assigned
+                .jumuah
+                .or(assigned.prayers.get(&Prayer::Dhuhr).cloned()),
+            &audio.adhan,
+        ),
+        (Signal::Chime(Chime::LeaveForJumuah), _) => {
+            (assigned.leave_for_jumuah.or(assigned.chime), &audio.chime)
+        }
+        (Signal::Chime(_), _) => (assigned.chime, &audio.chime),
+        _ => return None,
+    };
+
+    if let Some(name) = assigned {
+        match sounds.load(&name) {
+            Some(data) => return Some((name, data)),
+            None => tracing::error!("sound {:?} not found, playing {}", name, fallback),
+        }
+    }
+    sounds.load(fallback).map(|data| (fallback.clone(), data))
+}
+
+// ramp playback volume to target over duration
+fn fade(sink: &dyn Playback, target: f32, duration: std::time::Duration) {
+    const STEPS: u32 = 20;
+    if duration.is_zero() {
+        return;
+    }
+    let start = sink.volume();
+    for step in 1..=STEPS {
+        std::thread::sleep(duration / STEPS);
+        sink.set_volume(start + (target - start) * step as f32 / STEPS as f32);
     }
 }
 
-pub fn play_adhan(receiver: &crossbeam_channel::Receiver<(Signal, Prayer)>) {
-    while let Ok(_) = receiver.try_recv() {} // empty currently queued receiver messages
+pub fn play_adhan(
+    receiver: &crossbeam_channel::Receiver<Signals>,
+    audio: &AudioConfig,
+    sounds: &SoundLibrary,
+    settings: &SettingsStore,
+    status: &Arc<Status>,
+    backend: &dyn AudioBackend,
+) {
+    while receiver.try_recv().is_ok() {} // empty currently queued receiver messages
+
+    // alarms that stopped a paused adhan, played next
+    let interrupted: Arc<std::sync::Mutex<Option<Signals>>> = Arc::default();
 
     loop {
-        match receiver.recv().unwrap() {
-            (Signal::Play, prayer) => {
+        let pending = interrupted.lock().unwrap().take();
+        let signals = match pending {
+            Some(signals) => signals,
+            None => match receiver.recv() {
+                Ok(signals) => signals,
+                Err(_) => break,
+            },
+        };
+
+        // events sent together are played one after another; control signals are only handled
+        // while playing
+        let mut queued: Signals = vec![];
+        for (signal, prayer) in signals {
+            if let Signal::SetVolume(percent) = signal {
+                status.update_player(|player| player.volume = settings::percent_to_level(percent));
+            }
+            if signal.is_playable() && !queued.contains(&(signal, prayer)) {
+                queued.push((signal, prayer));
+            }
+        }
+        let prayer = match queued.first() {
+            Some((_, prayer)) => *prayer,
+            None => continue,
+        };
+
+        // volume profile, quiet hours and fades of the (first) prayer
+        let playback = settings.get();
+        let default_volume = playback.volume_level.unwrap_or(audio.volume);
+        let (volume, quiet) = playback.volume.playback(
+            prayer,
+            status.timezone().local(chrono::Utc::now()).time(),
+            default_volume,
+        );
+        let (fade_in, fade_out) = (playback.fade.fade_in(prayer), playback.fade.fade_out());
+
+        let sources: Vec<_> = queued
+            .into_iter()
+            .map(|(signal, prayer)| match signal {
+                Signal::Play | Signal::Jumuah if quiet && prayer.is_prayer() => {
+                    tracing::info!("quiet hours, playing chime instead of {:?} adhan", prayer);
+                    (Signal::Chime(Chime::QuietHours), prayer)
+                }
+                _ => (signal, prayer),
+            })
+            .filter_map(|(signal, prayer)| {
+                let (name, data) = match resolve_sound(signal, prayer, audio, sounds, settings) {
+                    Some(sound) => sound,
+                    None => {
+                        tracing::error!("no sound to play for {:?} signal", signal);
+                        return None;
+                    }
+                };
                 tracing::info!(
-                    "received play signal for prayer {:?}, playing adhan...",
-                    prayer
+                    "received {:?} signal for prayer {:?}, playing {}...",
+                    signal,
+                    prayer,
+                    name
                 );
+                Some((Track { name, data }, event_name(signal, prayer)))
+            })
+            .collect();
+        let (track, event) = match sources.first() {
+            Some((track, event)) => (track.name.to_owned(), event.to_owned()),
+            None => continue,
+        };
+
+        let tracks = sources.into_iter().map(|(track, _)| track).collect();
+        let output = match backend.play(tracks, volume, fade_in) {
+            Ok(output) => output,
+            Err(e) => {
+                tracing::error!("error playing {}: {}", track, e);
+                continue;
+            }
+        };
+        let sink = Arc::clone(&output.playback);
+        status.update_player(|player| player.play(track, prayer, event, volume));
 
-                while let Ok(_) = receiver.try_recv() {} // empty currently queued receiver messages
+        let receiver = receiver.clone();
+        let sink_ptr = Arc::clone(&sink);
+        let status_ptr = Arc::clone(status);
+        let interrupted_ptr = Arc::clone(&interrupted);
 
-                let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-                let sink = Arc::new(Sink::try_new(&stream_handle).unwrap());
-                let source_file = match prayer {
-                    Prayer::Fajr => Assets::get("adhan-fajr.mp3").unwrap(),
-                    _ => Assets::get("adhan-turkish.mp3").unwrap(),
-                };
-                let cursor = std::io::Cursor::new(source_file.data.to_owned());
-                let source = Decoder::new(BufReader::new(cursor)).unwrap();
-                sink.append(source);
-                sink.set_volume(5.0);
-
-                let receiver = receiver.clone();
-                let sink_ptr = Arc::clone(&sink);
-
-                std::thread::spawn(move || loop {
-                    match receiver.recv_timeout(std::time::Duration::from_secs(200)) {
-                        Ok((Signal::Stop, _)) => {
-                            tracing::info!("[thread] received stop signal for prayer {:?}...", prayer);
-                            if !sink_ptr.empty() {
-                                sink_ptr.stop();
+        // handles control signals until playback ends
+        std::thread::spawn(move || loop {
+            let signals = match receiver.recv_timeout(std::time::Duration::from_millis(200)) {
+                Ok(signals) => signals,
+                Err(crossbeam_channel::RecvTimeoutError::Timeout) if !sink_ptr.empty() => continue,
+                Err(_) => break,
+            };
+            for &(signal, _) in &signals {
+                match signal {
+                    Signal::Stop => {
+                        tracing::info!("[thread] received stop signal for prayer {:?}...", prayer);
+                        if !sink_ptr.empty() {
+                            // nothing to fade out while paused
+                            if !sink_ptr.is_paused() {
+                                fade(sink_ptr.as_ref(), 0.0, fade_out);
                             }
-                            break;
+                            sink_ptr.stop();
                         }
-                        Ok((Signal::Play, _)) => tracing::info!(
-                            "[thread] received play signal for prayer while already playing adhan..."
-                        ),
-                        Ok((Signal::VolumeUp, _)) => {
-                            tracing::info!("[thread] received volume up signal...");
-                            let volume = sink_ptr.volume();
-                            if volume < 15.0 {
-                                sink_ptr.set_volume(volume + 1.0);
-                                tracing::info!("[thread] volume set to {:?}", sink_ptr.volume());
-                            }
-                        },
-                        Ok((Signal::VolumeDown, _)) => {
-                            tracing::info!("[thread] received volume down signal...");
-                            let volume = sink_ptr.volume();
-                            if sink_ptr.volume() > 0.0 {
-                                sink_ptr.set_volume(volume - 1.0);
-                                tracing::info!("[thread] volume set to {:?}", sink_ptr.volume());
-                            }
-                        },
-                        Err(_) => {
-                            tracing::error!("[thread] timeout exceeded, cannot stop adhan...");
-                            break;
+                        return;
+                    }
+                    // a paused adhan gives way to the next alarm, rather than holding it up
+                    Signal::Play | Signal::Jumuah | Signal::Chime(_) if sink_ptr.is_paused() => {
+                        tracing::info!(
+                            "[thread] received play signal while paused, stopping adhan for prayer {:?}...",
+                            prayer
+                        );
+                        *interrupted_ptr.lock().unwrap() = Some(signals);
+                        sink_ptr.stop();
+                        return;
+                    }
+                    Signal::Play | Signal::Jumuah | Signal::Chime(_) => tracing::info!(
+                        "[thread] received play signal for prayer while already playing adhan..."
+                    ),
+                    Signal::VolumeUp => {
+                        tracing::info!("[thread] received volume up signal...");
+                        let volume = sink_ptr.volume();
+                        if volume < settings::MAX_VOLUME {
+                            sink_ptr.set_volume(volume + 1.0);
+                            tracing::info!("[thread] volume set to {:?}", sink_ptr.volume());
+                        }
+                    }
+                    Signal::Pause => {
+                        tracing::info!("[thread] pausing adhan for prayer {:?}...", prayer);
+                        sink_ptr.pause();
+                        status_ptr.update_player(PlayerStatus::pause);
+                    }
+                    Signal::Resume => {
+                        tracing::info!("[thread] resuming adhan for prayer {:?}...", prayer);
+                        sink_ptr.play();
+                        status_ptr.update_player(PlayerStatus::resume);
+                    }
+                    Signal::VolumeDown => {
+                        tracing::info!("[thread] received volume down signal...");
+                        let volume = sink_ptr.volume();
+                        if sink_ptr.volume() > 0.0 {
+                            sink_ptr.set_volume(volume - 1.0);
+                            tracing::info!("[thread] volume set to {:?}", sink_ptr.volume());
                         }
                     }
-                });
+                    Signal::SetVolume(percent) => {
+                        sink_ptr.set_volume(settings::percent_to_level(percent));
+                        tracing::info!("[thread] volume set to {:?}", sink_ptr.volume());
+                    }
+                }
+            }
+            status_ptr.update_player(|player| player.volume = sink_ptr.volume());
+        });
+
+        sink.sleep_until_end();
+        status.update_player(PlayerStatus::stop);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::RecordingBackend;
+    use crate::clock::FakeClock;
+    use crate::data::DataStore;
+    use crate::status::PlaybackState;
+    use std::collections::BTreeMap;
+
+    // same timings every day of the month
+    struct FixedProvider;
+
+    impl PrayerTimeProvider for FixedProvider {
+        fn monthly_timings(&self, params: &Params) -> Result<Vec<PrayerTime>, AdhanError> {
+            let timings: BTreeMap<String, Prayer> = [
+                ("04:30:00", Prayer::Imsak),
+                ("04:40:00", Prayer::Fajr),
+                ("06:30:00", Prayer::Sunrise),
+                ("12:30:00", Prayer::Dhuhr),
+                ("15:45:00", Prayer::Asr),
+                ("18:10:00", Prayer::Maghrib),
+                ("19:30:00", Prayer::Isha),
+            ]
+            .into_iter()
+            .map(|(time, prayer)| (time.to_owned(), prayer))
+            .collect();
+            let first = params.date.with_day(1).unwrap();
+            Ok(first
+                .iter_days()
+                .take_while(|day| day.month() == first.month())
+                .map(|day| PrayerTime {
+                    timezone: params.timezone,
+                    ..PrayerTime::new(day, 0, timings.clone())
+                })
+                .collect())
+        }
+    }
+
+    fn datetime(date: &str, time: &str) -> chrono::NaiveDateTime {
+        chrono::NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M")
+            .unwrap()
+    }
+
+    fn scheduler(
+        now: chrono::NaiveDateTime,
+    ) -> (
+        AdhanService,
+        Arc<FakeClock>,
+        crossbeam_channel::Receiver<Signals>,
+    ) {
+        let clock = Arc::new(FakeClock::new(now.and_utc()));
+        let (tx, rx) = crossbeam_channel::unbounded();
+        // times in the tests are utc, unless a test moves the location
+        let mut settings = Settings::default();
+        settings.params.timezone = Some(chrono_tz::UTC);
+        let service = AdhanService {
+            sender: tx,
+            database: Arc::new(DataStore::new()),
+            provider: Box::new(FixedProvider),
+            commands: crossbeam_channel::never(),
+            settings: Arc::new(SettingsStore::new(settings)),
+            status: Arc::new(Status::new(5.0)),
+            clock: Arc::clone(&clock) as Arc<dyn Clock>,
+            cache: MonthCache::open(None),
+            retry: Backoff::default(),
+        };
+        (service, clock, rx)
+    }
+
+    // runs the scheduler until a signal is sent; returns the (local) time it was sent at
+    fn next_signal(
+        service: &AdhanService,
+        clock: &FakeClock,
+        rx: &crossbeam_channel::Receiver<Signals>,
+    ) -> (chrono::NaiveDateTime, Signal, Prayer) {
+        for _ in 0..100 {
+            service.tick();
+            if let Ok(signals) = rx.try_recv() {
+                let [(signal, prayer)] = signals[..] else {
+                    panic!("more than one signal: {:?}", signals);
+                };
+                return (service.zone().local(clock.now()), signal, prayer);
+            }
+        }
+        panic!("no signal sent");
+    }
+
+    #[test]
+    fn test_day_of_signals() {
+        let (service, clock, rx) = scheduler(datetime("2026-10-18", "00:00"));
+        service.tick();
+
+        // fajr is switched off for the day; sunrise is off by default
+        let mut today = service.database.get(&"2026-10-18".to_owned()).unwrap();
+        today.play_adhan.insert(Prayer::Fajr, false);
+        service.database.set(&today.date.to_owned(), &today);
+
+        let signals: Vec<_> = (0..5).map(|_| next_signal(&service, &clock, &rx)).collect();
+        assert_eq!(
+            signals,
+            vec![
+                (datetime("2026-10-18", "12:30"), Signal::Play, Prayer::Dhuhr),
+                (datetime("2026-10-18", "15:45"), Signal::Play, Prayer::Asr),
+                (
+                    datetime("2026-10-18", "18:10"),
+                    Signal::Play,
+                    Prayer::Maghrib
+                ),
+                (datetime("2026-10-18", "19:30"), Signal::Play, Prayer::Isha),
+                (datetime("2026-10-19", "04:40"), Signal::Play, Prayer::Fajr),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_jumuah_replaces_dhuhr() {
+        let (service, _clock, _rx) = scheduler(datetime("2026-10-23", "00:00"));
+        service.tick();
+        let friday = service.database.get(&"2026-10-23".to_owned()).unwrap();
+        let date = datetime("2026-10-23", "00:00").date();
+        let dhuhr = |settings: &Settings| {
+            let mut friday = friday.clone();
+            apply_settings(&mut friday, settings);
+            day_events(&friday, date, settings)
+                .into_iter()
+                .filter(|(_, _, prayer)| *prayer == Prayer::Dhuhr)
+                .map(|(time, signal, _)| (time.format("%H:%M").to_string(), signal))
+                .collect::<Vec<_>>()
+        };
+
+        let mut settings = Settings::default();
+        settings.jumuah.enabled = true;
+        assert_eq!(dhuhr(&settings), vec![("12:30".to_owned(), Signal::Jumuah)]);
+        // unmuted, dhuhr is still not played twice at the same time
+        settings.jumuah.mute_dhuhr = false;
+        assert_eq!(dhuhr(&settings), vec![("12:30".to_owned(), Signal::Jumuah)]);
+        settings.jumuah.time = structs::IqamahTime::Offset(30);
+        assert_eq!(
+            dhuhr(&settings),
+            vec![
+                ("12:30".to_owned(), Signal::Play),
+                ("13:00".to_owned(), Signal::Jumuah)
+            ]
+        );
+
+        // the dhuhr reminder and iqamah go along with the adhan
+        settings.reminders.minutes.insert(Prayer::Dhuhr, 10);
+        settings.iqamah.schedules.insert(
+            Prayer::Dhuhr,
+            structs::IqamahSchedule {
+                time: structs::IqamahTime::Offset(15),
+                weekdays: Default::default(),
+            },
+        );
+        assert_eq!(
+            dhuhr(&settings),
+            vec![
+                ("12:20".to_owned(), Signal::Chime(Chime::Reminder)),
+                ("12:30".to_owned(), Signal::Play),
+                ("13:00".to_owned(), Signal::Jumuah),
+                ("12:45".to_owned(), Signal::Chime(Chime::Iqamah))
+            ]
+        );
+        settings.jumuah.mute_dhuhr = true;
+        assert_eq!(dhuhr(&settings), vec![("13:00".to_owned(), Signal::Jumuah)]);
+        settings.jumuah.mute_dhuhr = false;
+        settings.jumuah.time = structs::IqamahTime::Offset(0);
+        assert_eq!(dhuhr(&settings), vec![("12:30".to_owned(), Signal::Jumuah)]);
+    }
+
+    #[test]
+    fn test_imsak_in_ramadan() {
+        let (service, clock, rx) = scheduler(datetime("2026-10-18", "20:00"));
+        service.tick();
+        let mut tomorrow = service.database.get(&"2026-10-19".to_owned()).unwrap();
+        tomorrow.play_adhan.insert(Prayer::Imsak, true);
+        service.database.set(&tomorrow.date.to_owned(), &tomorrow);
+
+        // not ramadan by the provider's (missing) hijri date
+        assert_eq!(
+            next_signal(&service, &clock, &rx),
+            (datetime("2026-10-19", "04:40"), Signal::Play, Prayer::Fajr)
+        );
+
+        let (service, clock, rx) = scheduler(datetime("2026-10-18", "20:00"));
+        service.settings.update(|settings| {
+            settings.ramadan.mode = settings::RamadanMode::On;
+            settings.ramadan.suhoor = 30;
+        });
+        service.tick();
+        let mut tomorrow = service.database.get(&"2026-10-19".to_owned()).unwrap();
+        tomorrow.play_adhan.insert(Prayer::Imsak, true);
+        service.database.set(&tomorrow.date.to_owned(), &tomorrow);
+        let signals = (0..3).map(|_| next_signal(&service, &clock, &rx));
+        assert_eq!(
+            signals.collect::<Vec<_>>(),
+            vec![
+                (
+                    datetime("2026-10-19", "04:00"),
+                    Signal::Chime(Chime::Ramadan(RamadanEvent::Suhoor)),
+                    Prayer::Fajr
+                ),
+                (datetime("2026-10-19", "04:30"), Signal::Play, Prayer::Imsak),
+                (datetime("2026-10-19", "04:40"), Signal::Play, Prayer::Fajr),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_month_rollover() {
+        let (service, clock, rx) = scheduler(datetime("2026-10-31", "22:00"));
+        assert_eq!(
+            next_signal(&service, &clock, &rx),
+            (datetime("2026-11-01", "04:40"), Signal::Play, Prayer::Fajr)
+        );
+        // days that have passed are dropped
+        service.tick();
+        assert!(service.database.get(&"2026-10-31".to_owned()).is_none());
+    }
+
+    // fails the first few fetches
+    struct FlakyProvider(std::sync::atomic::AtomicU32);
+
+    impl PrayerTimeProvider for FlakyProvider {
+        fn monthly_timings(&self, params: &Params) -> Result<Vec<PrayerTime>, AdhanError> {
+            use std::sync::atomic::Ordering;
+            match self
+                .0
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            {
+                Ok(_) => Err(AdhanError::MissingTiming(Prayer::Isha)),
+                Err(_) => FixedProvider.monthly_timings(params),
+            }
+        }
+    }
+
+    #[test]
+    fn test_retry_with_backoff() {
+        let (service, clock, _rx) = scheduler(datetime("2026-10-18", "00:00"));
+        let service = AdhanService {
+            provider: Box::new(FlakyProvider(2.into())),
+            ..service
+        };
+        // nothing to fall back on without coordinates or a cached month
+        service
+            .settings
+            .update(|settings| settings.params.coordinates = None);
+
+        service.tick();
+        let error = service.status.error().unwrap();
+        assert_eq!(error.kind, "missing_timing");
+        assert_eq!(error.message, "missing Isha timing");
+        assert_eq!(
+            clock.now().naive_utc(),
+            datetime("2026-10-18", "00:00") + chrono::Duration::seconds(5)
+        );
+        service.tick();
+        assert_eq!(
+            clock.now().naive_utc(),
+            datetime("2026-10-18", "00:00") + chrono::Duration::seconds(15)
+        );
+
+        service.tick();
+        assert!(service.status.error().is_none());
+        assert_eq!(service.status.next()[0].prayer, Prayer::Fajr);
+    }
+
+    #[test]
+    fn test_reset_keeps_toggles() {
+        let (service, _clock, _rx) = scheduler(datetime("2026-10-18", "00:00"));
+        service.tick();
+        let mut today = service.database.get(&"2026-10-18".to_owned()).unwrap();
+        today.play_adhan.insert(Prayer::Asr, false);
+        service.database.set(&today.date.to_owned(), &today);
+
+        service.handle_command(Command::Reset);
+        let today = service.database.get(&"2026-10-18".to_owned()).unwrap();
+        assert_eq!(today.play_adhan.get(&Prayer::Asr), Some(&false));
+        assert!(service.database.get(&"2026-10-31".to_owned()).is_some());
+    }
+
+    #[test]
+    fn test_fallback() {
+        let (service, clock, rx) = scheduler(datetime("2026-10-18", "00:00"));
+        let service = AdhanService {
+            provider: Box::new(FlakyProvider(1.into())),
+            ..service
+        };
+
+        // offline calculation until the retry succeeds
+        service.tick();
+        assert_eq!(service.status.fallback(), Some("calculator"));
+        assert!(service.database.get(&"2026-10-18".to_owned()).is_some());
+        service.tick();
+        assert_eq!(service.status.fallback(), None);
+        assert_eq!(
+            next_signal(&service, &clock, &rx),
+            (datetime("2026-10-18", "04:40"), Signal::Play, Prayer::Fajr)
+        );
+
+        // the last month fetched is used next time the provider fails
+        service
+            .settings
+            .update(|settings| settings.params.coordinates = None);
+        let service = AdhanService {
+            provider: Box::new(FlakyProvider(1.into())),
+            database: Arc::new(DataStore::new()),
+            ..service
+        };
+        service.tick();
+        assert_eq!(service.status.fallback(), Some("cache"));
+        // the fixed provider's sunrise, not the calculated one
+        assert_eq!(
+            service.status.next()[0].datetime.naive_local(),
+            datetime("2026-10-18", "06:30")
+        );
+    }
+
+    #[test]
+    fn test_fallback_of_one_month() {
+        // october fails and falls back; november (prefetched) is fetched fine
+        let (service, _clock, _rx) = scheduler(datetime("2026-10-31", "22:00"));
+        let service = AdhanService {
+            provider: Box::new(FlakyProvider(1.into())),
+            ..service
+        };
+        service.tick();
+        assert!(service.database.get(&"2026-11-01".to_owned()).is_some());
+        assert_eq!(service.status.fallback(), Some("calculator"));
+        assert!(service.status.error().is_some());
+        assert!(service.retry.retry_at().is_some());
+
+        // both months are fetched again on the retry
+        service.tick();
+        assert_eq!(service.status.fallback(), None);
+        assert!(service.retry.retry_at().is_none());
+    }
+
+    // fixed timings, reported in zone
+    struct ZonedProvider(chrono_tz::Tz);
+
+    impl PrayerTimeProvider for ZonedProvider {
+        fn monthly_timings(&self, params: &Params) -> Result<Vec<PrayerTime>, AdhanError> {
+            FixedProvider.monthly_timings(&Params {
+                timezone: Some(self.0),
+                ..params.clone()
+            })
+        }
+    }
+
+    #[test]
+    fn test_provider_timezone() {
+        // no zone configured; the api's is used over the system's, by the calculator as well
+        let (service, _clock, _rx) = scheduler(datetime("2026-10-18", "00:00"));
+        service
+            .settings
+            .update(|settings| settings.params.timezone = None);
+        let service = AdhanService {
+            provider: Box::new(ZonedProvider(chrono_tz::Pacific::Auckland)),
+            ..service
+        };
+        service
+            .load_prayer_timings(datetime("2026-10-18", "00:00").date())
+            .unwrap();
+        assert_eq!(service.zone(), Zone::Iana(chrono_tz::Pacific::Auckland));
+
+        let service = AdhanService {
+            provider: Box::new(FlakyProvider(1.into())),
+            ..service
+        };
+        service
+            .load_prayer_timings(datetime("2026-11-01", "00:00").date())
+            .unwrap();
+        assert_eq!(service.status.fallback(), Some("calculator"));
+        let november = service.database.get(&"2026-11-01".to_owned()).unwrap();
+        assert_eq!(november.timezone, Some(chrono_tz::Pacific::Auckland));
+        // fajr before dawn in auckland, not in utc
+        let (fajr, _) = november
+            .timings
+            .iter()
+            .find(|(_, prayer)| **prayer == Prayer::Fajr)
+            .unwrap();
+        assert!(
+            fajr.as_str() > "04:00" && fajr.as_str() < "05:00",
+            "{}",
+            fajr
+        );
+    }
 
-                sink.sleep_until_end();
+    #[test]
+    fn test_daylight_saving() {
+        // clocks go forward an hour at 01:00 GMT; fajr at 04:40 BST is 03:40 UTC
+        let (service, clock, rx) = scheduler(datetime("2026-03-29", "00:00"));
+        service
+            .settings
+            .update(|settings| settings.params.timezone = Some(chrono_tz::Europe::London));
+        assert_eq!(
+            next_signal(&service, &clock, &rx),
+            (datetime("2026-03-29", "04:40"), Signal::Play, Prayer::Fajr)
+        );
+        assert_eq!(clock.now().naive_utc(), datetime("2026-03-29", "03:40"));
+        let next = &service.status.next()[0];
+        assert_eq!(next.datetime.to_rfc3339(), "2026-03-29T04:40:00+01:00");
+
+        // clocks go back an hour at 02:00 BST; fajr at 04:40 GMT is 04:40 UTC
+        let (service, clock, rx) = scheduler(datetime("2026-10-25", "00:00"));
+        service
+            .settings
+            .update(|settings| settings.params.timezone = Some(chrono_tz::Europe::London));
+        assert_eq!(
+            next_signal(&service, &clock, &rx),
+            (datetime("2026-10-25", "04:40"), Signal::Play, Prayer::Fajr)
+        );
+        assert_eq!(clock.now().naive_utc(), datetime("2026-10-25", "04:40"));
+
+        // timings are stored with their zone, which is used if none is configured
+        service
+            .settings
+            .update(|settings| settings.params.timezone = None);
+        assert_eq!(service.zone(), Zone::Iana(chrono_tz::Europe::London));
+    }
+
+    #[test]
+    fn test_repeated_hour() {
+        // 01:10 happens at 00:10 and at 01:10 UTC when clocks go back; the alarm only fires once
+        let (service, clock, rx) = scheduler(datetime("2026-10-25", "00:00"));
+        service
+            .settings
+            .update(|settings| settings.params.timezone = Some(chrono_tz::Europe::London));
+        service
+            .load_prayer_timings(datetime("2026-10-25", "00:00").date())
+            .unwrap();
+        let mut today = service.database.get(&"2026-10-25".to_owned()).unwrap();
+        today
+            .timings
+            .insert("01:10:00".to_owned(), Prayer::LastThird);
+        today.play_adhan.insert(Prayer::LastThird, true);
+        service.database.set(&today.date.to_owned(), &today);
+
+        assert_eq!(
+            next_signal(&service, &clock, &rx),
+            (
+                datetime("2026-10-25", "01:10"),
+                Signal::Play,
+                Prayer::LastThird
+            )
+        );
+        assert_eq!(clock.now().naive_utc(), datetime("2026-10-25", "00:10"));
+
+        // woken in the repeated hour, e.g. by a restart
+        clock.set(datetime("2026-10-25", "01:05").and_utc());
+        assert_eq!(
+            next_signal(&service, &clock, &rx),
+            (datetime("2026-10-25", "04:40"), Signal::Play, Prayer::Fajr)
+        );
+    }
+
+    // polls until f holds, for at most a second
+    fn wait_until(f: impl Fn() -> bool) {
+        for _ in 0..100 {
+            if f() {
+                return;
             }
-            _ => (),
+            std::thread::sleep(std::time::Duration::from_millis(10));
         }
+        panic!("timed out waiting");
+    }
+
+    // player on the recording backend, playing test.mp3 for adhans and sample.mp3 for chimes
+    fn player() -> (
+        crossbeam_channel::Sender<Signals>,
+        Arc<Status>,
+        Arc<RecordingBackend>,
+    ) {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let status = Arc::new(Status::new(5.0));
+        let backend = Arc::new(RecordingBackend::new(std::time::Duration::from_secs(60)));
+        {
+            let (status, backend) = (Arc::clone(&status), Arc::clone(&backend));
+            std::thread::spawn(move || {
+                let audio = AudioConfig {
+                    fajr: "test.mp3".to_owned(),
+                    adhan: "test.mp3".to_owned(),
+                    ..AudioConfig::default()
+                };
+                let sounds = SoundLibrary::new(std::env::temp_dir().join("prayer-alarm-none"));
+                let settings = SettingsStore::new(Settings::default());
+                play_adhan(&rx, &audio, &sounds, &settings, &status, backend.as_ref());
+            });
+        }
+        (tx, status, backend)
+    }
+
+    #[test]
+    fn test_player() {
+        let (tx, status, backend) = player();
+
+        // messages queued before the player starts are dropped; extra play signals are ignored
+        wait_until(|| {
+            tx.send(vec![(Signal::Play, Prayer::Fajr)]).unwrap();
+            status.player().state == PlaybackState::Playing
+        });
+        assert_eq!(status.player().track, Some("test.mp3".to_owned()));
+
+        for signal in [Signal::VolumeUp, Signal::SetVolume(100), Signal::Pause] {
+            tx.send(vec![(signal, Prayer::Fajr)]).unwrap();
+        }
+        wait_until(|| status.player().state == PlaybackState::Paused);
+        tx.send(vec![(Signal::Resume, Prayer::Fajr)]).unwrap();
+        tx.send(vec![(Signal::Stop, Prayer::Fajr)]).unwrap();
+        wait_until(|| status.player().state == PlaybackState::Idle);
+
+        let recordings = backend.recordings();
+        assert_eq!(recordings.len(), 1);
+        assert_eq!(recordings[0].tracks, vec!["test.mp3"]);
+        assert_eq!(recordings[0].volumes, vec![5.0, 6.0, 15.0]);
+        assert!(recordings[0].paused && recordings[0].stopped);
+        assert!(recordings[0].played.unwrap() < std::time::Duration::from_secs(60));
+        assert_eq!(status.player().volume, 15.0);
+    }
+
+    #[test]
+    fn test_events_played_together() {
+        // iftar is due with the maghrib adhan; both are sent in one message
+        let (service, clock, rx) = scheduler(datetime("2026-10-18", "17:00"));
+        service.tick();
+        let mut today = service.database.get(&"2026-10-18".to_owned()).unwrap();
+        today
+            .ramadan
+            .insert("18:10:00".to_owned(), RamadanEvent::Iftar);
+        today.play_ramadan.insert(RamadanEvent::Iftar, true);
+        service.database.set(&today.date.to_owned(), &today);
+        while clock.now().naive_utc() < datetime("2026-10-18", "18:10") {
+            service.tick();
+        }
+        let signals = rx.try_recv().unwrap();
+        assert_eq!(
+            signals,
+            vec![
+                (
+                    Signal::Chime(Chime::Ramadan(RamadanEvent::Iftar)),
+                    Prayer::Maghrib
+                ),
+                (Signal::Play, Prayer::Maghrib)
+            ]
+        );
+
+        let (tx, status, backend) = player();
+        wait_until(|| {
+            tx.send(signals.clone()).unwrap();
+            status.player().state == PlaybackState::Playing
+        });
+        assert_eq!(
+            backend.recordings()[0].tracks,
+            vec!["sample.mp3", "test.mp3"]
+        );
+    }
+
+    #[test]
+    fn test_alarm_while_paused() {
+        // a paused adhan that is never resumed does not hold up the next alarm
+        let (tx, status, backend) = player();
+        wait_until(|| {
+            tx.send(vec![(Signal::Play, Prayer::Fajr)]).unwrap();
+            status.player().state == PlaybackState::Playing
+        });
+        tx.send(vec![(Signal::Pause, Prayer::Fajr)]).unwrap();
+        wait_until(|| status.player().state == PlaybackState::Paused);
+
+        tx.send(vec![(Signal::Play, Prayer::Dhuhr)]).unwrap();
+        wait_until(|| status.player().prayer == Some(Prayer::Dhuhr));
+        assert_eq!(status.player().state, PlaybackState::Playing);
+        let recordings = backend.recordings();
+        assert_eq!(recordings.len(), 2);
+        assert!(recordings[0].paused && recordings[0].stopped);
+        assert!(!recordings[1].stopped);
     }
 }
 
@@ -1,20 +1,38 @@
 // #![allow(unused)] // For beginning only.
 
 use axum::{
-    extract::{Json, Path, State},
+    body::Bytes,
+    extract::{DefaultBodyLimit, Json, Path, Query, State},
     http::StatusCode,
-    response::{Html, IntoResponse, Response},
-    routing::{get, post, put},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Html, IntoResponse, Response,
+    },
+    routing::{delete, get, post, put},
     Router,
 };
 use prayer_alarm::{
-    data::{DataStore, Database},
-    structs::{Params, Prayer, PrayerTime},
-    AdhanService, Signal,
+    audio::BackendKind,
+    calc::Method,
+    clock::SystemClock,
+    config::{Config, ProviderKind},
+    data::{DataStore, Database, FileStore},
+    hijri,
+    provider::MonthCache,
+    rules::MuteRule,
+    settings::{
+        self, FadeSettings, IqamahSettings, JumuahSettings, RamadanSettings, ReminderSettings,
+        Settings, SettingsStore, SoundSettings, VolumeSettings,
+    },
+    sounds::SoundLibrary,
+    status::{Notification, Status},
+    structs::{Params, Prayer, PrayerTime, RamadanEvent},
+    AdhanService, Backoff, Command, Signal, Signals,
 };
 use rust_embed::RustEmbed;
 use serde_json::{json, Value};
-use std::{collections::HashMap, sync::Arc};
+use std::sync::Arc;
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
 
 // // get month and/or year if any params are None
 // let (month, year) = match (self.month, self.year) {
@@ -35,55 +53,253 @@ use std::{collections::HashMap, sync::Arc};
 //     None => "0,0,0,0,0,0,0,0".to_string(),
 // };
 
+// largest accepted sound upload
+const MAX_SOUND_SIZE: usize = 50 * 1024 * 1024;
+
 #[derive(Clone)]
 struct AppState {
     database: Arc<dyn Database<PrayerTime, Key = String>>,
-    tx: crossbeam_channel::Sender<(Signal, Prayer)>,
+    tx: crossbeam_channel::Sender<Signals>,
+    commands: crossbeam_channel::Sender<Command>,
+    settings: Arc<SettingsStore>,
+    sounds: Arc<SoundLibrary>,
+    status: Arc<Status>,
+    // configured volume (sink level), until one is set via the api
+    default_volume: f32,
+}
+
+impl AppState {
+    // tell `/events` subscribers that a settings section was updated
+    fn config_changed(&self, section: &str) {
+        self.status.publish(Notification::ConfigChanged {
+            section: section.to_owned(),
+        });
+    }
+}
+
+#[derive(clap::Parser, Debug)]
+#[command(about = "Plays the adhan at prayer times")]
+struct Cli {
+    /// Config file (toml); see config.example.toml
+    #[arg(short, long, env = "CONFIG")]
+    config: Option<std::path::PathBuf>,
+    /// Address the http server listens on [default: 0.0.0.0:3000]
+    #[arg(long, env = "LISTEN")]
+    listen: Option<std::net::SocketAddr>,
+    /// Directory for persisted timings and settings (in memory only if unset)
+    #[arg(long, env = "DATA_DIR")]
+    data_dir: Option<std::path::PathBuf>,
+    /// Log level: trace, debug, info, warn or error [default: info]
+    #[arg(long, env = "LOG_LEVEL")]
+    log_level: Option<String>,
+    /// Source of prayer timings [default: aladhan]
+    #[arg(long, env = "PROVIDER", value_enum)]
+    provider: Option<ProviderKind>,
+    /// Timetable file (csv/json) for the timetable provider
+    #[arg(long, env = "TIMETABLE")]
+    timetable: Option<std::path::PathBuf>,
+    /// City of the location
+    #[arg(long, requires = "country")]
+    city: Option<String>,
+    /// Country of the location
+    #[arg(long, requires = "city")]
+    country: Option<String>,
+    /// Latitude of the location (takes precedence over city/country)
+    #[arg(long, requires = "longitude", allow_negative_numbers = true)]
+    latitude: Option<f64>,
+    /// Longitude of the location
+    #[arg(long, requires = "latitude", allow_negative_numbers = true)]
+    longitude: Option<f64>,
+    /// Calculation method id (as used by api.aladhan.com) [default: 3]
+    #[arg(long)]
+    method: Option<u8>,
+    /// Default playback volume [default: 5.0]
+    #[arg(long)]
+    volume: Option<f32>,
+    /// Audio output device name (system default if unset)
+    #[arg(long)]
+    audio_device: Option<String>,
+    /// Audio output; `null` plays nothing (e.g. without a sound card) [default: rodio]
+    #[arg(long, env = "AUDIO_BACKEND", value_enum)]
+    audio_backend: Option<BackendKind>,
+}
+
+impl Cli {
+    // command line flags take precedence over the config file
+    fn into_config(self) -> Result<Config, String> {
+        let mut config = match &self.config {
+            Some(path) => Config::load(path)?,
+            None => Config::default(),
+        };
+        if let Some(listen) = self.listen {
+            config.listen = listen;
+        }
+        if let Some(data_dir) = self.data_dir {
+            config.data_dir = Some(data_dir);
+        }
+        if let Some(log_level) = self.log_level {
+            config.log_level = log_level;
+        }
+        if let Some(provider) = self.provider {
+            config.provider = provider;
+        }
+        if let Some(timetable) = self.timetable {
+            config.timetable = Some(timetable);
+        }
+        if let (Some(city), Some(country)) = (self.city, self.country) {
+            config.location.city = city;
+            config.location.country = country;
+            config.location.coordinates = None;
+        }
+        if let (Some(latitude), Some(longitude)) = (self.latitude, self.longitude) {
+            config.location.coordinates = Some((latitude, longitude));
+        }
+        if let Some(method) = self.method {
+            config.location.method = method;
+        }
+        if let Some(volume) = self.volume {
+            config.audio.volume = volume;
+        }
+        if let Some(audio_device) = self.audio_device {
+            config.audio.device = Some(audio_device);
+        }
+        if let Some(audio_backend) = self.audio_backend {
+            config.audio.backend = audio_backend;
+        }
+        // refuse to start on a typo rather than logging at the wrong level
+        config.max_log_level()?;
+        Ok(config)
+    }
 }
 
 #[tokio::main]
 async fn main() {
-    // TODO clap
+    let config = match <Cli as clap::Parser>::parse().into_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
-    tracing_subscriber::fmt::init();
+    tracing_subscriber::fmt()
+        .with_max_level(
+            config
+                .max_log_level()
+                .expect("log level checked in into_config"),
+        )
+        .init();
 
-    let (tx, rx) = crossbeam_channel::unbounded::<(Signal, Prayer)>();
+    let (tx, rx) = crossbeam_channel::unbounded::<Signals>();
+    let (commands_tx, commands_rx) = crossbeam_channel::unbounded::<Command>();
 
+    // timings and toggles persist across restarts if a data directory is configured
     let database: Arc<dyn Database<PrayerTime, Key = String>> =
-        Arc::new(DataStore::<PrayerTime>::new());
+        match config.data_file("prayer-times.json") {
+            Some(path) => {
+                Arc::new(FileStore::<PrayerTime>::open(path).expect("error opening database"))
+            }
+            None => Arc::new(DataStore::<PrayerTime>::new()),
+        };
+
+    // so do location, method and other settings changed at runtime
+    let defaults = Settings {
+        params: config.location.clone(),
+        ..Settings::default()
+    };
+    let settings = Arc::new(match config.data_file("settings.json") {
+        Some(path) => SettingsStore::open(path, defaults).expect("error opening settings"),
+        None => SettingsStore::new(defaults),
+    });
+
+    // uploaded sounds; kept in a temporary directory if no data directory is configured
+    let sounds = Arc::new(SoundLibrary::new(
+        config
+            .data_file("sounds")
+            .unwrap_or_else(|| std::env::temp_dir().join("prayer-alarm-sounds")),
+    ));
+
+    let status = Arc::new(Status::new(
+        settings.get().volume_level.unwrap_or(config.audio.volume),
+    ));
 
     let state = AppState {
         database: Arc::clone(&database),
         tx: tx.clone(),
+        commands: commands_tx,
+        settings: Arc::clone(&settings),
+        sounds: Arc::clone(&sounds),
+        status: Arc::clone(&status),
+        default_volume: config.audio.volume,
     };
 
-    let params = Params::new("Auckland", "NewZealand");
+    let provider = config.provider();
+
     let service = AdhanService {
-        params,
         sender: tx,
         database,
+        provider,
+        commands: commands_rx,
+        settings: Arc::clone(&settings),
+        status: Arc::clone(&status),
+        clock: Arc::new(SystemClock),
+        cache: MonthCache::open(config.data_file("last-month.json")),
+        retry: Backoff::default(),
     };
 
     // TODO: use tokio::spawn
     // tokio::task::spawn(move || service.init_prayer_alarm());
     std::thread::spawn(move || service.init_prayer_alarm());
-    std::thread::spawn(move || prayer_alarm::play_adhan(&rx));
+    let audio = config.audio.clone();
+    let backend = config.audio_backend();
+    std::thread::spawn(move || {
+        prayer_alarm::play_adhan(&rx, &audio, &sounds, &settings, &status, backend.as_ref())
+    });
 
     let app = Router::new()
         .route("/", get(index_handler))
         .route("/index.html", get(index_handler))
         .route("/health", get(health))
+        .route("/status", get(get_status))
+        .route("/events", get(get_events))
         .route("/timings", get(get_timings).post(post_timings))
         .route("/timings/:date/:prayer", put(put_timings_prayer))
+        .route("/iqamah", get(get_iqamah).put(put_iqamah))
+        .route("/calendar", get(get_calendar))
+        .route("/reminders", get(get_reminders).put(put_reminders))
+        .route("/ramadan", get(get_ramadan).put(put_ramadan))
+        .route("/jumuah", get(get_jumuah).put(put_jumuah))
+        .route("/rules", get(get_rules).post(post_rule))
+        .route("/rules/:id", delete(delete_rule))
+        .route("/config", get(get_config).put(put_config))
+        .route("/sounds", get(get_sounds))
+        .route(
+            "/sounds/:name",
+            put(put_sound)
+                .delete(delete_sound)
+                .layer(DefaultBodyLimit::max(MAX_SOUND_SIZE)),
+        )
+        .route(
+            "/sounds/assignments",
+            get(get_sound_assignments).put(put_sound_assignments),
+        )
+        .route(
+            "/volume/profiles",
+            get(get_volume_profiles).put(put_volume_profiles),
+        )
+        .route("/volume/fade", get(get_fade).put(put_fade))
         .route("/play", post(play_adhan))
+        .route("/volume", get(get_volume).put(put_volume))
         .route("/volume-up", post(volume_up))
         .route("/volume-down", post(volume_down))
+        .route("/pause", post(pause_adhan))
+        .route("/resume", post(resume_adhan))
         .route("/halt", post(stop_adhan))
         .route("/reset", post(reset_adhan_timings))
         .fallback_service(get(not_found))
         .with_state(state);
 
-    let addr = std::net::SocketAddr::from(([0, 0, 0, 0], 3000));
+    let addr = config.listen;
     tracing::info!("listening on {}....", addr);
     axum::Server::bind(&addr)
         .serve(app.into_make_service())
@@ -112,7 +328,40 @@ async fn health() -> Json<Value> {
     Json(json!({ "status": "up" }))
 }
 
+// `curl -X GET http://localhost:3000/status`
+// Note: countdown is in seconds until the next scheduled event
+async fn get_status(State(state): State<AppState>) -> impl IntoResponse {
+    let next = state.status.next();
+    let countdown = next.first().map(|event| {
+        (event.datetime.with_timezone(&chrono::Utc) - chrono::Utc::now())
+            .num_seconds()
+            .max(0)
+    });
+    Json(json!({
+        "player": state.status.player(),
+        "next": next,
+        "countdown": countdown,
+        "error": state.status.error(),
+        "timezone": state.status.timezone().name(),
+        "degraded": state.status.fallback().is_some(),
+        "fallback": state.status.fallback(),
+    }))
+}
+
+// `curl -N http://localhost:3000/events`
+// Note: server-sent events, one json notification per event e.g. {"type": "playback_started", ...}
+async fn get_events(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, serde_json::Error>>> {
+    // notifications missed by a lagging client are skipped
+    let stream = BroadcastStream::new(state.status.subscribe())
+        .filter_map(|notification| notification.ok())
+        .map(|notification| Event::default().json_data(notification));
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
 // `curl -X GET http://localhost:3000/timings`
+// Note: times are wall clock times in the `timezone` of each day
 async fn get_timings(State(state): State<AppState>) -> impl IntoResponse {
     let prayer_times = state.database.get_all();
     Json(prayer_times)
@@ -120,7 +369,39 @@ async fn get_timings(State(state): State<AppState>) -> impl IntoResponse {
 
 #[derive(serde::Deserialize)]
 struct UpdatePrayerTiming {
-    play_adhan: bool,
+    play_adhan: Option<bool>,
+    play_iqamah: Option<bool>,
+    play_reminder: Option<bool>,
+    // ramadan alarms belonging to the prayer e.g. iftar for maghrib
+    play_ramadan: Option<bool>,
+}
+
+impl UpdatePrayerTiming {
+    // apply toggles to prayers of prayer time
+    fn apply(&self, prayer_time: &mut PrayerTime, prayers: &[Prayer]) {
+        for prayer in prayers {
+            if let Some(play_adhan) = self.play_adhan {
+                prayer_time.play_adhan.insert(*prayer, play_adhan);
+            }
+            if let Some(play_iqamah) = self.play_iqamah {
+                prayer_time.play_iqamah.insert(*prayer, play_iqamah);
+            }
+            if let Some(play_reminder) = self.play_reminder {
+                prayer_time.play_reminder.insert(*prayer, play_reminder);
+            }
+            if let Some(play_ramadan) = self.play_ramadan {
+                for event in [
+                    RamadanEvent::Suhoor,
+                    RamadanEvent::Iftar,
+                    RamadanEvent::Taraweeh,
+                ] {
+                    if event.prayer() == *prayer {
+                        prayer_time.play_ramadan.insert(event, play_ramadan);
+                    }
+                }
+            }
+        }
+    }
 }
 
 // `curl -X POST -H "Content-Type: application/json" --data '{"play_adhan": false}' http://localhost:3000/timings`
@@ -129,25 +410,26 @@ async fn post_timings(
     Json(payload): Json<UpdatePrayerTiming>,
 ) -> impl IntoResponse {
     tracing::info!(
-        "setting all prayer times to play_adhan: {}",
-        payload.play_adhan
+        "setting all prayer times to play_adhan: {:?}, play_iqamah: {:?}",
+        payload.play_adhan,
+        payload.play_iqamah
     );
 
     let modified_prayers_times: Vec<PrayerTime> = state
         .database
         .get_all()
-        .iter()
-        .map(|prayer_time| {
-            // set all values of the play_adhan hashmap to payload
-            let play_adhan: HashMap<Prayer, bool> = prayer_time
-                .play_adhan
-                .iter()
-                .map(|(key, _)| (*key, payload.play_adhan))
+        .into_iter()
+        .map(|mut prayer_time| {
+            // set the five prayers of the play_adhan/play_iqamah hashmaps to payload; other
+            // events (sunrise, imsak etc.) are toggled individually
+            let prayers: Vec<Prayer> = prayer_time
+                .timings
+                .values()
+                .copied()
+                .filter(Prayer::is_prayer)
                 .collect();
-            PrayerTime {
-                play_adhan,
-                ..prayer_time.clone()
-            }
+            payload.apply(&mut prayer_time, &prayers);
+            prayer_time
         })
         .collect();
     let prayer_keys = modified_prayers_times
@@ -171,48 +453,459 @@ async fn put_timings_prayer(
         .get(&prayer_date)
         .ok_or((StatusCode::NOT_FOUND, "failed".to_owned()))?;
 
-    let prayer: Prayer = Prayer::from_str(prayer.as_str())
-        .ok_or((StatusCode::BAD_REQUEST, "invalid prayer name".to_owned()))?;
+    let prayer: Prayer = prayer
+        .parse()
+        .map_err(|_| (StatusCode::BAD_REQUEST, "invalid prayer name".to_owned()))?;
 
-    prayer_time.play_adhan.insert(prayer, payload.play_adhan);
+    payload.apply(&mut prayer_time, &[prayer]);
     state.database.set(&prayer_date, &prayer_time);
     Ok((StatusCode::ACCEPTED, "success"))
 }
 
+// `curl -X GET http://localhost:3000/iqamah`
+async fn get_iqamah(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.settings.get().iqamah)
+}
+
+// `curl -X PUT -H "Content-Type: application/json" --data '{"schedules": {"Dhuhr": {"time": {"offset": 15}, "weekdays": {"Fri": {"fixed": "13:30:00"}}}}, "chime": true}' http://localhost:3000/iqamah`
+async fn put_iqamah(
+    State(state): State<AppState>,
+    Json(payload): Json<IqamahSettings>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    payload
+        .validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    tracing::info!("updating iqamah settings: {:?}", payload);
+    state.settings.update(|settings| settings.iqamah = payload);
+    state.config_changed("iqamah");
+    state.commands.send(Command::Refresh).unwrap();
+    Ok((StatusCode::ACCEPTED, ()))
+}
+
+#[derive(serde::Deserialize)]
+struct CalendarQuery {
+    // YYYY-MM; current month if unset
+    month: Option<String>,
+}
+
+// `curl -X GET http://localhost:3000/calendar?month=2023-03`
+// Note: hijri dates of stored days come from the provider, the others from the offline calendar
+async fn get_calendar(
+    State(state): State<AppState>,
+    Query(query): Query<CalendarQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let date = match query.month {
+        Some(month) => chrono::NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+            .map_err(|_| (StatusCode::BAD_REQUEST, "invalid month".to_owned()))?,
+        None => state.status.timezone().local(chrono::Utc::now()).date(),
+    };
+
+    let adjustment = state.settings.get().params.hijri_adjustment;
+    let days: Vec<hijri::CalendarDay> = hijri::month(date, adjustment)
+        .into_iter()
+        .map(|day| match state.database.get(&day.date) {
+            Some(PrayerTime {
+                hijri: Some(hijri),
+                holidays,
+                ..
+            }) => hijri::CalendarDay {
+                hijri,
+                holidays,
+                ..day
+            },
+            _ => day,
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "month": date.format("%Y-%m").to_string(),
+        "days": days,
+    })))
+}
+
+// `curl -X GET http://localhost:3000/reminders`
+async fn get_reminders(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.settings.get().reminders)
+}
+
+// `curl -X PUT -H "Content-Type: application/json" --data '{"minutes": {"Fajr": 10, "Maghrib": 5}}' http://localhost:3000/reminders`
+async fn put_reminders(
+    State(state): State<AppState>,
+    Json(payload): Json<ReminderSettings>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    if payload
+        .minutes
+        .values()
+        .any(|minutes| !(1..=180).contains(minutes))
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "reminder minutes must be between 1 and 180".to_owned(),
+        ));
+    }
+
+    tracing::info!("updating reminder settings: {:?}", payload);
+    state
+        .settings
+        .update(|settings| settings.reminders = payload);
+    state.config_changed("reminders");
+    state.commands.send(Command::Refresh).unwrap();
+    Ok((StatusCode::ACCEPTED, "success"))
+}
+
+// `curl -X GET http://localhost:3000/ramadan`
+async fn get_ramadan(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.settings.get().ramadan)
+}
+
+// `curl -X PUT -H "Content-Type: application/json" --data '{"mode": "auto", "suhoor": 45, "iftar": true, "taraweeh": 30}' http://localhost:3000/ramadan`
+async fn put_ramadan(
+    State(state): State<AppState>,
+    Json(payload): Json<RamadanSettings>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut minutes = std::iter::once(payload.suhoor).chain(payload.taraweeh);
+    if minutes.any(|minutes| !(0..=180).contains(&minutes)) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "suhoor and taraweeh minutes must be between 0 and 180".to_owned(),
+        ));
+    }
+
+    tracing::info!("updating ramadan settings: {:?}", payload);
+    state.settings.update(|settings| settings.ramadan = payload);
+    state.config_changed("ramadan");
+    state.commands.send(Command::Refresh).unwrap();
+    Ok((StatusCode::ACCEPTED, "success"))
+}
+
+// `curl -X GET http://localhost:3000/jumuah`
+async fn get_jumuah(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.settings.get().jumuah)
+}
+
+// `curl -X PUT -H "Content-Type: application/json" --data '{"enabled": true, "time": {"fixed": "13:30:00"}, "leave_reminder": 30, "mute_dhuhr": true}' http://localhost:3000/jumuah`
+async fn put_jumuah(
+    State(state): State<AppState>,
+    Json(payload): Json<JumuahSettings>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    payload
+        .validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    tracing::info!("updating jumu'ah settings: {:?}", payload);
+    state.settings.update(|settings| settings.jumuah = payload);
+    state.config_changed("jumuah");
+    state.commands.send(Command::Refresh).unwrap();
+    Ok((StatusCode::ACCEPTED, "success"))
+}
+
+// `curl -X GET http://localhost:3000/rules`
+async fn get_rules(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.settings.get().mute_rules)
+}
+
+// `curl -X POST -H "Content-Type: application/json" --data '{"name": "work", "prayers": ["Dhuhr"], "events": ["adhan"], "weekdays": ["Mon", "Tue", "Wed", "Thu", "Fri"]}' http://localhost:3000/rules`
+// `curl -X POST -H "Content-Type: application/json" --data '{"name": "vacation", "from": "2026-12-20", "until": "2027-01-05"}' http://localhost:3000/rules`
+async fn post_rule(
+    State(state): State<AppState>,
+    Json(mut payload): Json<MuteRule>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    payload
+        .validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    tracing::info!("adding mute rule: {:?}", payload);
+    state.settings.update(|settings| {
+        payload.id = settings
+            .mute_rules
+            .iter()
+            .map(|rule| rule.id)
+            .max()
+            .unwrap_or(0)
+            + 1;
+        settings.mute_rules.push(payload.clone());
+    });
+    state.config_changed("rules");
+    Ok((StatusCode::CREATED, Json(payload)))
+}
+
+// `curl -X DELETE http://localhost:3000/rules/1`
+async fn delete_rule(
+    Path(id): Path<u32>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut deleted = false;
+    state.settings.update(|settings| {
+        let count = settings.mute_rules.len();
+        settings.mute_rules.retain(|rule| rule.id != id);
+        deleted = settings.mute_rules.len() < count;
+    });
+    if !deleted {
+        return Err((StatusCode::NOT_FOUND, "no such rule".to_owned()));
+    }
+    tracing::info!("deleted mute rule {}", id);
+    state.config_changed("rules");
+    Ok((StatusCode::ACCEPTED, "success"))
+}
+
+// `curl -X GET http://localhost:3000/config`
+async fn get_config(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.settings.get().params)
+}
+
+// `curl -X PUT -H "Content-Type: application/json" --data '{"city": "London", "country": "UK", "coordinates": [51.5074, -0.1278], "method": 3, "school": "Hanafi", "offsets": [0, 0, 0, 2, 0], "hijri_adjustment": -1}' http://localhost:3000/config`
+async fn put_config(
+    State(state): State<AppState>,
+    Json(payload): Json<Params>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    if Method::from_id(payload.method).is_none() {
+        return Err((StatusCode::BAD_REQUEST, "invalid method".to_owned()));
+    }
+    if !(-2..=2).contains(&payload.hijri_adjustment) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "hijri adjustment must be between -2 and 2 days".to_owned(),
+        ));
+    }
+    if let Some((latitude, longitude)) = payload.coordinates {
+        if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+            return Err((StatusCode::BAD_REQUEST, "invalid coordinates".to_owned()));
+        }
+    }
+
+    tracing::info!("updating config: {:?}", payload);
+    state.settings.update(|settings| settings.params = payload);
+    state.config_changed("config");
+    state.commands.send(Command::Reload).unwrap();
+    Ok((StatusCode::ACCEPTED, "success"))
+}
+
+// `curl -X GET http://localhost:3000/sounds`
+async fn get_sounds(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.sounds.list())
+}
+
+// `curl -X PUT --data-binary @adhan-makkah.mp3 http://localhost:3000/sounds/adhan-makkah.mp3`
+async fn put_sound(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    body: Bytes,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    tracing::info!("uploading sound {} ({} bytes)", name, body.len());
+    state
+        .sounds
+        .save(&name, &body)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    Ok((StatusCode::CREATED, "success"))
+}
+
+// `curl -X DELETE http://localhost:3000/sounds/adhan-makkah.mp3`
+// Note: embedded sounds cannot be deleted; prayers assigned the sound fall back to the defaults
+async fn delete_sound(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let deleted = state
+        .sounds
+        .delete(&name)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    if !deleted {
+        return Err((StatusCode::NOT_FOUND, "no such uploaded sound".to_owned()));
+    }
+
+    tracing::info!("deleted sound {}", name);
+    state.settings.update(|settings| {
+        let sounds = &mut settings.sounds;
+        sounds.prayers.retain(|_, assigned| *assigned != name);
+        sounds.reminders.retain(|_, assigned| *assigned != name);
+        sounds.ramadan.retain(|_, assigned| *assigned != name);
+        for assigned in [
+            &mut sounds.chime,
+            &mut sounds.jumuah,
+            &mut sounds.leave_for_jumuah,
+        ] {
+            if assigned.as_ref() == Some(&name) {
+                *assigned = None;
+            }
+        }
+    });
+    state.config_changed("sounds");
+    Ok((StatusCode::ACCEPTED, "success"))
+}
+
+// `curl -X GET http://localhost:3000/sounds/assignments`
+async fn get_sound_assignments(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.settings.get().sounds)
+}
+
+// `curl -X PUT -H "Content-Type: application/json" --data '{"prayers": {"Fajr": "adhan-makkah.mp3"}, "chime": "sample.mp3", "reminders": {"Fajr": "fajr-soon.mp3"}}' http://localhost:3000/sounds/assignments`
+async fn put_sound_assignments(
+    State(state): State<AppState>,
+    Json(payload): Json<SoundSettings>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    if let Some(name) = payload
+        .prayers
+        .values()
+        .chain(payload.chime.iter())
+        .chain(payload.reminders.values())
+        .chain(payload.ramadan.values())
+        .chain(payload.jumuah.iter())
+        .chain(payload.leave_for_jumuah.iter())
+        .find(|name| !state.sounds.contains(name))
+    {
+        return Err((StatusCode::BAD_REQUEST, format!("unknown sound: {}", name)));
+    }
+
+    tracing::info!("updating sound assignments: {:?}", payload);
+    state.settings.update(|settings| settings.sounds = payload);
+    state.config_changed("sounds");
+    Ok((StatusCode::ACCEPTED, "success"))
+}
+
+// `curl -X GET http://localhost:3000/volume/profiles`
+async fn get_volume_profiles(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.settings.get().volume)
+}
+
+// `curl -X PUT -H "Content-Type: application/json" --data '{"active": "kids", "profiles": [{"name": "kids", "volume": 4.0, "prayers": {"Fajr": 2.0, "Isha": 2.0, "Dhuhr": 8.0}}], "quiet_hours": [{"start": "22:00:00", "end": "05:00:00", "action": "chime"}]}' http://localhost:3000/volume/profiles`
+// Note: volumes are sink levels from 0 to 15
+async fn put_volume_profiles(
+    State(state): State<AppState>,
+    Json(payload): Json<VolumeSettings>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    payload
+        .validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    tracing::info!("updating volume settings: {:?}", payload);
+    state.settings.update(|settings| settings.volume = payload);
+    state.config_changed("volume");
+    Ok((StatusCode::ACCEPTED, "success"))
+}
+
+// `curl -X GET http://localhost:3000/volume/fade`
+async fn get_fade(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.settings.get().fade)
+}
+
+// `curl -X PUT -H "Content-Type: application/json" --data '{"fade_in": 3.0, "fade_out": 2.0, "prayers": {"Fajr": 15.0}}' http://localhost:3000/volume/fade`
+async fn put_fade(
+    State(state): State<AppState>,
+    Json(payload): Json<FadeSettings>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    payload
+        .validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    tracing::info!("updating fade settings: {:?}", payload);
+    state.settings.update(|settings| settings.fade = payload);
+    state.config_changed("fade");
+    Ok((StatusCode::ACCEPTED, "success"))
+}
+
 // `curl -X POST http://localhost:3000/play`
 // Note: post request takes empty payload
 async fn play_adhan(State(state): State<AppState>) -> impl IntoResponse {
     tracing::warn!("playing adhan...");
-    state.tx.send((Signal::Play, Prayer::Dhuhr)).unwrap();
+    state.tx.send(vec![(Signal::Play, Prayer::Dhuhr)]).unwrap();
     (StatusCode::ACCEPTED, ())
 }
 
+#[derive(serde::Deserialize)]
+struct UpdateVolume {
+    // percent
+    volume: u8,
+}
+
+// `curl -X GET http://localhost:3000/volume`
+async fn get_volume(State(state): State<AppState>) -> impl IntoResponse {
+    let level = state
+        .settings
+        .get()
+        .volume_level
+        .unwrap_or(state.default_volume);
+    Json(json!({
+        "volume": settings::level_to_percent(level),
+        "level": level,
+    }))
+}
+
+// `curl -X PUT -H "Content-Type: application/json" --data '{"volume": 40}' http://localhost:3000/volume`
+// Note: applies to the adhan playing now and is the default for future playbacks
+async fn put_volume(
+    State(state): State<AppState>,
+    Json(payload): Json<UpdateVolume>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    if payload.volume > 100 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "volume must be between 0 and 100".to_owned(),
+        ));
+    }
+
+    tracing::info!("setting volume to {}%...", payload.volume);
+    let level = settings::percent_to_level(payload.volume);
+    state
+        .settings
+        .update(|settings| settings.volume_level = Some(level));
+    state
+        .tx
+        .send(vec![(Signal::SetVolume(payload.volume), Prayer::Dhuhr)])
+        .unwrap();
+    Ok((StatusCode::ACCEPTED, "success"))
+}
+
 // `curl -X POST http://localhost:3000/volume-up`
 async fn volume_up(State(state): State<AppState>) -> impl IntoResponse {
     tracing::warn!("increasing volume...");
-    state.tx.send((Signal::VolumeUp, Prayer::Dhuhr)).unwrap();
+    state
+        .tx
+        .send(vec![(Signal::VolumeUp, Prayer::Dhuhr)])
+        .unwrap();
     (StatusCode::ACCEPTED, ())
 }
 
 // `curl -X POST http://localhost:3000/volume-down`
 async fn volume_down(State(state): State<AppState>) -> impl IntoResponse {
     tracing::warn!("decreasing volume...");
-    state.tx.send((Signal::VolumeDown, Prayer::Dhuhr)).unwrap();
+    state
+        .tx
+        .send(vec![(Signal::VolumeDown, Prayer::Dhuhr)])
+        .unwrap();
+    (StatusCode::ACCEPTED, ())
+}
+
+// `curl -X POST http://localhost:3000/pause`
+async fn pause_adhan(State(state): State<AppState>) -> impl IntoResponse {
+    tracing::warn!("pausing running adhan...");
+    state.tx.send(vec![(Signal::Pause, Prayer::Dhuhr)]).unwrap();
+    (StatusCode::ACCEPTED, ())
+}
+
+// `curl -X POST http://localhost:3000/resume`
+async fn resume_adhan(State(state): State<AppState>) -> impl IntoResponse {
+    tracing::warn!("resuming paused adhan...");
+    state
+        .tx
+        .send(vec![(Signal::Resume, Prayer::Dhuhr)])
+        .unwrap();
     (StatusCode::ACCEPTED, ())
 }
 
 // `curl -X POST http://localhost:3000/halt`
 async fn stop_adhan(State(state): State<AppState>) -> impl IntoResponse {
     tracing::warn!("stopping running adhan...");
-    state.tx.send((Signal::Stop, Prayer::Dhuhr)).unwrap();
+    state.tx.send(vec![(Signal::Stop, Prayer::Dhuhr)]).unwrap();
     (StatusCode::ACCEPTED, ())
 }
 
 // `curl -X POST http://localhost:3000/reset`
 // Note: post request takes empty payload
-async fn reset_adhan_timings() -> impl IntoResponse {
-    tracing::warn!("resetting adhan timings; killing process...");
-    std::process::exit(1);
+async fn reset_adhan_timings(State(state): State<AppState>) -> impl IntoResponse {
+    tracing::warn!("resetting adhan timings...");
+    state.commands.send(Command::Reset).unwrap();
+    (StatusCode::ACCEPTED, ())
 }
 
 // Finally, we use a fallback route for anything that didn't match.
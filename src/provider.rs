@@ -0,0 +1,355 @@
+// Sources of prayer timings.
+//
+// `AdhanService` only ever asks a provider for the timings of a month; where they come from (the
+// aladhan api, the offline calculator or a mosque's published timetable) is up to the backend.
+use chrono::Datelike;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::calc::Calculator;
+use crate::data::write_json;
+use crate::error::AdhanError;
+use crate::hijri;
+use crate::structs::{api, Params, Prayer, PrayerTime};
+use crate::timezone::Zone;
+
+pub trait PrayerTimeProvider: Sync + Send {
+    // prayer timings for every day in the month of `params.date`, each day with the zone its times
+    // are in (calculated in `params.timezone`, unless the provider knows better)
+    fn monthly_timings(&self, params: &Params) -> Result<Vec<PrayerTime>, AdhanError>;
+}
+
+// http://api.aladhan.com calendar api
+pub struct AladhanProvider;
+
+impl PrayerTimeProvider for AladhanProvider {
+    fn monthly_timings(&self, params: &Params) -> Result<Vec<PrayerTime>, AdhanError> {
+        let api_url = params.to_prayer_timings_url();
+
+        let response = reqwest::blocking::get(api_url)?;
+        if !response.status().is_success() {
+            return Err(AdhanError::Status(response.status()));
+        }
+        let monthly_prayer_timings: api::PrayerCalendarResponse =
+            serde_json::from_str(&response.text()?)?;
+
+        monthly_prayer_timings
+            .data
+            .into_iter()
+            .map(PrayerTime::try_from)
+            .collect()
+    }
+}
+
+// offline astronomical calculation; requires `params.coordinates`
+pub struct LocalProvider;
+
+impl PrayerTimeProvider for LocalProvider {
+    fn monthly_timings(&self, params: &Params) -> Result<Vec<PrayerTime>, AdhanError> {
+        let (latitude, longitude) = params.coordinates.ok_or_else(|| {
+            AdhanError::Provider("local calculation requires coordinates".to_owned())
+        })?;
+        let mut calculator = Calculator::new(latitude, longitude, params.method)
+            .ok_or(format!("unsupported calculation method: {}", params.method))?;
+        calculator.school = params.school;
+        calculator.offsets = params.offsets;
+        let zone = zone(params);
+
+        days_of_month(params.date)
+            .map(|date| {
+                let noon = zone.instant(date.and_hms_opt(12, 0, 0).unwrap());
+                let utc_offset = zone.with_offset(noon).offset().local_minus_utc();
+                let timings = calculator
+                    .timings(date, utc_offset)
+                    .ok_or(format!("no sunrise/sunset on {}", date))?;
+
+                let timings = [
+                    (timings.imsak, Prayer::Imsak),
+                    (timings.fajr, Prayer::Fajr),
+                    (timings.sunrise, Prayer::Sunrise),
+                    (timings.dhuhr, Prayer::Dhuhr),
+                    (timings.asr, Prayer::Asr),
+                    (timings.maghrib, Prayer::Maghrib),
+                    (timings.isha, Prayer::Isha),
+                    (timings.midnight, Prayer::Midnight),
+                    (timings.lastthird, Prayer::LastThird),
+                ]
+                .into_iter()
+                .map(|(time, prayer)| (time.to_string(), prayer))
+                .collect();
+
+                let mut prayer_time = PrayerTime::new(date, timestamp(zone, date), timings);
+                prayer_time.timezone = params.timezone;
+                set_hijri(&mut prayer_time, date, params.hijri_adjustment);
+                Ok(prayer_time)
+            })
+            .collect()
+    }
+}
+
+// mosque timetable file; `.json` files are read as a list of rows, anything else as csv with the
+// header `date,fajr,dhuhr,asr,maghrib,isha` (dates as YYYY-MM-DD, times as HH:MM)
+pub struct TimetableProvider {
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct TimetableRow {
+    date: String,
+    fajr: String,
+    dhuhr: String,
+    asr: String,
+    maghrib: String,
+    isha: String,
+}
+
+impl PrayerTimeProvider for TimetableProvider {
+    fn monthly_timings(&self, params: &Params) -> Result<Vec<PrayerTime>, AdhanError> {
+        let contents = std::fs::read_to_string(&self.path)
+            .map_err(|e| format!("Error reading timetable {:?}: {:?}", self.path, e))?;
+
+        let rows = match self.path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&contents)
+                .map_err(|e| format!("Error parsing timetable: {:?}", e))?,
+            _ => parse_csv(&contents)?,
+        };
+
+        let month = params.date.format("%Y-%m-").to_string();
+        let zone = zone(params);
+        let mut prayer_times = rows
+            .into_iter()
+            .map(|row| row.into_prayer_time(zone))
+            .collect::<Result<Vec<PrayerTime>, AdhanError>>()?;
+        prayer_times.retain(|prayer_time| prayer_time.date.starts_with(&month));
+        prayer_times.sort_by(|a, b| a.date.cmp(&b.date));
+        for prayer_time in prayer_times.iter_mut() {
+            prayer_time.timezone = params.timezone;
+            if let Ok(date) = chrono::NaiveDate::parse_from_str(&prayer_time.date, "%Y-%m-%d") {
+                set_hijri(prayer_time, date, params.hijri_adjustment);
+            }
+        }
+
+        if prayer_times.is_empty() {
+            return Err(format!(
+                "timetable has no entries for {}",
+                params.date.format("%Y-%m")
+            )
+            .into());
+        }
+        Ok(prayer_times)
+    }
+}
+
+impl TimetableRow {
+    fn into_prayer_time(self, zone: Zone) -> Result<PrayerTime, AdhanError> {
+        let date = chrono::NaiveDate::parse_from_str(self.date.trim(), "%Y-%m-%d")
+            .map_err(|_| AdhanError::parse("date", self.date.trim()))?;
+
+        let timings = [
+            (self.fajr, Prayer::Fajr),
+            (self.dhuhr, Prayer::Dhuhr),
+            (self.asr, Prayer::Asr),
+            (self.maghrib, Prayer::Maghrib),
+            (self.isha, Prayer::Isha),
+        ]
+        .into_iter()
+        .map(|(time, prayer)| {
+            chrono::NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .map(|time| (time.to_string(), prayer))
+                .map_err(|_| AdhanError::parse("time", time.trim()))
+        })
+        .collect::<Result<BTreeMap<String, Prayer>, AdhanError>>()?;
+
+        Ok(PrayerTime::new(date, timestamp(zone, date), timings))
+    }
+}
+
+fn parse_csv(contents: &str) -> Result<Vec<TimetableRow>, String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .skip(1) // header
+        .map(|line| {
+            let columns: Vec<String> = line.split(',').map(|c| c.trim().to_owned()).collect();
+            match columns.as_slice() {
+                [date, fajr, dhuhr, asr, maghrib, isha, ..] => Ok(TimetableRow {
+                    date: date.to_owned(),
+                    fajr: fajr.to_owned(),
+                    dhuhr: dhuhr.to_owned(),
+                    asr: asr.to_owned(),
+                    maghrib: maghrib.to_owned(),
+                    isha: isha.to_owned(),
+                }),
+                _ => Err(format!("invalid timetable row: {:?}", line)),
+            }
+        })
+        .collect()
+}
+
+// last month fetched successfully, to fall back on when the provider fails; kept on disk if a path
+// is given
+pub struct MonthCache {
+    path: Option<PathBuf>,
+    month: Mutex<Vec<PrayerTime>>,
+}
+
+impl MonthCache {
+    // a missing or unreadable file is an empty cache
+    pub fn open(path: Option<PathBuf>) -> Self {
+        let month = path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            month: Mutex::new(month),
+        }
+    }
+
+    pub fn save(&self, month: &[PrayerTime]) {
+        if let Some(path) = &self.path {
+            if let Err(e) = write_json(path, &month) {
+                tracing::error!("Error writing cache file {:?}: {:?}", path, e);
+            }
+        }
+        *self.month.lock().unwrap() = month.to_vec();
+    }
+
+    // zone the cached month was fetched in
+    pub fn timezone(&self) -> Option<chrono_tz::Tz> {
+        self.month
+            .lock()
+            .unwrap()
+            .iter()
+            .find_map(|prayer_time| prayer_time.timezone)
+    }
+
+    // cached timings if they are of the month of `params.date`
+    pub fn exact(&self, params: &Params) -> Option<Vec<PrayerTime>> {
+        let month = self.month.lock().unwrap();
+        let prefix = params.date.format("%Y-%m-").to_string();
+        match month.first() {
+            Some(first) if first.date.starts_with(&prefix) => Some(month.clone()),
+            _ => None,
+        }
+    }
+
+    // timings of another month, reused for the month of `params.date` day by day (the last cached
+    // day standing in for days the cached month does not have) - close enough for a few weeks
+    pub fn approximate(&self, params: &Params) -> Option<Vec<PrayerTime>> {
+        let month = self.month.lock().unwrap();
+        if month.is_empty() {
+            return None;
+        }
+        let zone = month[0]
+            .timezone
+            .map(Zone::Iana)
+            .unwrap_or_else(|| zone(params));
+        let prayer_times = days_of_month(params.date)
+            .map(|date| {
+                let cached = &month[(date.day0() as usize).min(month.len() - 1)];
+                let mut prayer_time =
+                    PrayerTime::new(date, timestamp(zone, date), cached.timings.clone());
+                prayer_time.timezone = cached.timezone;
+                set_hijri(&mut prayer_time, date, params.hijri_adjustment);
+                prayer_time
+            })
+            .collect();
+        Some(prayer_times)
+    }
+}
+
+// hijri date and holidays from the offline calendar
+fn set_hijri(prayer_time: &mut PrayerTime, date: chrono::NaiveDate, adjustment: i8) {
+    let hijri = hijri::from_gregorian(date, adjustment);
+    prayer_time.holidays = hijri::holidays(&hijri);
+    prayer_time.hijri = Some(hijri);
+}
+
+fn days_of_month(date: chrono::NaiveDate) -> impl Iterator<Item = chrono::NaiveDate> {
+    let first = date.with_day(1).unwrap();
+    first
+        .iter_days()
+        .take_while(move |day| day.month() == first.month())
+}
+
+// zone the timings of params are calculated in
+fn zone(params: &Params) -> Zone {
+    params.timezone.map(Zone::Iana).unwrap_or_else(Zone::system)
+}
+
+// unix timestamp of local midnight for date
+fn timestamp(zone: Zone, date: chrono::NaiveDate) -> u32 {
+    zone.instant(date.and_hms_opt(0, 0, 0).unwrap()).timestamp() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv_timetable() {
+        let csv = "date,fajr,dhuhr,asr,maghrib,isha\n\
+                   2022-11-02, 04:50, 13:15, 17:00, 20:00, 21:30\n\
+                   2022-11-01, 04:45, 13:15, 17:00, 19:58, 21:30\n";
+        let rows = parse_csv(csv).unwrap();
+        assert_eq!(rows.len(), 2);
+
+        let prayer_time = rows
+            .into_iter()
+            .nth(1)
+            .unwrap()
+            .into_prayer_time(Zone::System)
+            .unwrap();
+        assert_eq!(prayer_time.date, "2022-11-01");
+        assert_eq!(prayer_time.timings.get("04:45:00"), Some(&Prayer::Fajr));
+        assert_eq!(prayer_time.timings.get("19:58:00"), Some(&Prayer::Maghrib));
+    }
+
+    #[test]
+    fn test_parse_csv_invalid_row() {
+        assert!(parse_csv("date,fajr\n2022-11-01,04:45").is_err());
+    }
+
+    #[test]
+    fn test_local_provider_month() {
+        let mut params = Params::new("Auckland", "NewZealand");
+        params.coordinates = Some((-36.8484597, 174.7633315));
+        params.date = chrono::NaiveDate::from_ymd_opt(2022, 2, 10).unwrap();
+
+        let prayer_times = LocalProvider.monthly_timings(&params).unwrap();
+        assert_eq!(prayer_times.len(), 28);
+        assert_eq!(prayer_times[0].date, "2022-02-01");
+        assert_eq!(prayer_times[0].timings.len(), 9);
+    }
+
+    #[test]
+    fn test_month_cache() {
+        let path = std::env::temp_dir().join(format!("prayer-alarm-cache-{}", std::process::id()));
+        let mut params = Params::new("Auckland", "NewZealand");
+        params.coordinates = Some((-36.8484597, 174.7633315));
+        params.date = chrono::NaiveDate::from_ymd_opt(2022, 1, 10).unwrap();
+        let january = LocalProvider.monthly_timings(&params).unwrap();
+
+        let cache = MonthCache::open(Some(path.clone()));
+        assert!(cache.approximate(&params).is_none());
+        cache.save(&january);
+
+        // survives a restart
+        let cache = MonthCache::open(Some(path.clone()));
+        assert_eq!(cache.exact(&params).unwrap().len(), 31);
+
+        params.date = chrono::NaiveDate::from_ymd_opt(2022, 2, 10).unwrap();
+        assert!(cache.exact(&params).is_none());
+        let february = cache.approximate(&params).unwrap();
+        assert_eq!(february.len(), 28);
+        assert_eq!(february[27].date, "2022-02-28");
+        assert_eq!(february[27].timings, january[27].timings);
+        assert!(february[27].hijri.is_some());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,108 @@
+// Recurring mute rules e.g. "never play fajr", "mute dhuhr on weekdays" or "mute everything during
+// a vacation"; evaluated when an event fires, so they apply to timings fetched later as well.
+use chrono::Datelike;
+use serde::{Deserialize, Serialize};
+
+use crate::structs::Prayer;
+
+// kinds of scheduled events
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    Adhan,
+    Iqamah,
+    Reminder,
+    Ramadan,
+    Jumuah,
+}
+
+// a rule mutes events matching all of its conditions; empty/unset conditions match everything
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct MuteRule {
+    pub id: u32,
+    pub name: String,
+    pub prayers: Vec<Prayer>,
+    pub events: Vec<EventKind>,
+    pub weekdays: Vec<chrono::Weekday>,
+    // inclusive date range
+    pub from: Option<chrono::NaiveDate>,
+    pub until: Option<chrono::NaiveDate>,
+}
+
+impl MuteRule {
+    pub fn matches(&self, kind: EventKind, prayer: Prayer, date: chrono::NaiveDate) -> bool {
+        (self.prayers.is_empty() || self.prayers.contains(&prayer))
+            && (self.events.is_empty() || self.events.contains(&kind))
+            && (self.weekdays.is_empty() || self.weekdays.contains(&date.weekday()))
+            && self.from.map(|from| date >= from).unwrap_or(true)
+            && self.until.map(|until| date <= until).unwrap_or(true)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match (self.from, self.until) {
+            (Some(from), Some(until)) if from > until => {
+                Err(format!("rule ends ({}) before it starts ({})", until, from))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+pub fn is_muted(
+    rules: &[MuteRule],
+    kind: EventKind,
+    prayer: Prayer,
+    date: chrono::NaiveDate,
+) -> bool {
+    rules.iter().any(|rule| rule.matches(kind, prayer, date))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mute_rules() {
+        let rules: Vec<MuteRule> = serde_json::from_str(
+            r#"[
+                {"name": "never play fajr", "prayers": ["Fajr"], "events": ["adhan"]},
+                {"name": "work", "prayers": ["Dhuhr"], "weekdays": ["Mon", "Tue", "Wed", "Thu", "Fri"]},
+                {"name": "vacation", "from": "2026-12-20", "until": "2027-01-05"}
+            ]"#,
+        )
+        .unwrap();
+        let date = |y, m, d| chrono::NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let monday = date(2026, 11, 2);
+        let saturday = date(2026, 11, 7);
+
+        assert!(is_muted(&rules, EventKind::Adhan, Prayer::Fajr, saturday));
+        assert!(!is_muted(&rules, EventKind::Iqamah, Prayer::Fajr, saturday));
+
+        assert!(is_muted(&rules, EventKind::Adhan, Prayer::Dhuhr, monday));
+        assert!(!is_muted(&rules, EventKind::Adhan, Prayer::Dhuhr, saturday));
+
+        assert!(is_muted(
+            &rules,
+            EventKind::Ramadan,
+            Prayer::Isha,
+            date(2027, 1, 5)
+        ));
+        assert!(!is_muted(
+            &rules,
+            EventKind::Adhan,
+            Prayer::Isha,
+            date(2027, 1, 6)
+        ));
+    }
+
+    #[test]
+    fn test_validate() {
+        let rule = MuteRule {
+            from: chrono::NaiveDate::from_ymd_opt(2027, 1, 5),
+            until: chrono::NaiveDate::from_ymd_opt(2026, 12, 20),
+            ..MuteRule::default()
+        };
+        assert!(rule.validate().is_err());
+    }
+}
@@ -0,0 +1,521 @@
+// User configurable behaviour of the alarm; shared between the scheduler and the http api.
+use chrono::Datelike;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use crate::rules::MuteRule;
+use crate::structs::{IqamahSchedule, IqamahTime, Params, Prayer, RamadanEvent};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    // location and calculation method
+    pub params: Params,
+    pub iqamah: IqamahSettings,
+    pub reminders: ReminderSettings,
+    pub ramadan: RamadanSettings,
+    pub jumuah: JumuahSettings,
+    pub mute_rules: Vec<MuteRule>,
+    // default volume (sink level) set via the api; the configured audio volume if unset
+    pub volume_level: Option<f32>,
+    pub volume: VolumeSettings,
+    pub fade: FadeSettings,
+    pub sounds: SoundSettings,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct IqamahSettings {
+    // prayers without a schedule have no iqamah time
+    pub schedules: HashMap<Prayer, IqamahSchedule>,
+    // play a chime at iqamah time
+    pub chime: bool,
+}
+
+impl IqamahSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        self.schedules
+            .values()
+            .try_for_each(IqamahSchedule::validate)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ReminderSettings {
+    // minutes before the adhan e.g. `{"Fajr": 10}`; prayers without an entry have no reminder
+    pub minutes: HashMap<Prayer, i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RamadanSettings {
+    pub mode: RamadanMode,
+    // suhoor alarm, minutes before imsak (or fajr if there is no imsak time)
+    pub suhoor: i64,
+    // announce iftar at maghrib
+    pub iftar: bool,
+    // taraweeh reminder, minutes after isha
+    pub taraweeh: Option<i64>,
+}
+
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RamadanMode {
+    // on during the hijri month of ramadan (requires a provider with hijri dates)
+    #[default]
+    Auto,
+    On,
+    Off,
+}
+
+impl Default for RamadanSettings {
+    fn default() -> Self {
+        Self {
+            mode: RamadanMode::Auto,
+            suhoor: 45,
+            iftar: true,
+            taraweeh: None,
+        }
+    }
+}
+
+// friday prayer; replaces dhuhr on fridays when enabled
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct JumuahSettings {
+    pub enabled: bool,
+    // fixed time e.g. `{"fixed": "13:30:00"}` or minutes after the dhuhr adhan e.g. `{"offset": 0}`
+    pub time: IqamahTime,
+    // "leave for jumu'ah" reminder, minutes before jumu'ah
+    pub leave_reminder: Option<i64>,
+    // skip the regular dhuhr adhan on fridays; it is always skipped if jumu'ah is at the same time
+    pub mute_dhuhr: bool,
+}
+
+impl Default for JumuahSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            time: IqamahTime::Offset(0),
+            leave_reminder: None,
+            mute_dhuhr: true,
+        }
+    }
+}
+
+impl JumuahSettings {
+    // jumu'ah and leave reminder times on date; none if disabled, not a friday or if an offset would
+    // move a time to another day
+    pub fn times(
+        &self,
+        date: chrono::NaiveDate,
+        dhuhr: chrono::NaiveTime,
+    ) -> Option<(chrono::NaiveTime, Option<chrono::NaiveTime>)> {
+        if !self.enabled || date.weekday() != chrono::Weekday::Fri {
+            return None;
+        }
+        let jumuah = match self.time {
+            IqamahTime::Fixed(time) => time,
+            IqamahTime::Offset(minutes) => {
+                match dhuhr.overflowing_add_signed(chrono::Duration::minutes(minutes)) {
+                    (jumuah, 0) => jumuah,
+                    _ => return None,
+                }
+            }
+        };
+        let leave = self.leave_reminder.and_then(|minutes| {
+            match jumuah.overflowing_sub_signed(chrono::Duration::minutes(minutes)) {
+                (leave, 0) => Some(leave),
+                _ => None,
+            }
+        });
+        Some((jumuah, leave))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.time.validate()?;
+        match self.leave_reminder {
+            Some(minutes) if !(1..=MAX_LEAVE_REMINDER).contains(&minutes) => Err(format!(
+                "leave reminder minutes must be between 1 and {}",
+                MAX_LEAVE_REMINDER
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+// earliest "leave for jumu'ah" reminder, in minutes before jumu'ah
+const MAX_LEAVE_REMINDER: i64 = 180;
+
+// volume profiles and quiet hours; volumes are sink levels (0 - 15)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct VolumeSettings {
+    // name of the profile in use; the configured default volume applies if unset
+    pub active: Option<String>,
+    pub profiles: Vec<VolumeProfile>,
+    pub quiet_hours: Vec<QuietHours>,
+}
+
+// e.g. softer fajr and isha when kids are asleep, louder dhuhr
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct VolumeProfile {
+    pub name: String,
+    // prayers without a volume of their own; the configured default volume if unset
+    pub volume: Option<f32>,
+    pub prayers: HashMap<Prayer, f32>,
+}
+
+// window of the day, e.g. `{"start": "22:00:00", "end": "06:00:00", "action": "chime"}`; may span
+// midnight
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QuietHours {
+    pub start: chrono::NaiveTime,
+    pub end: chrono::NaiveTime,
+    pub action: QuietAction,
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum QuietAction {
+    // play the chime instead of the adhan
+    Chime,
+    // cap the volume e.g. `{"volume": 2.0}`
+    Volume(f32),
+}
+
+impl QuietHours {
+    pub fn contains(&self, time: chrono::NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl VolumeSettings {
+    // volume of prayer's sounds at time, and whether an adhan should be replaced by the chime
+    pub fn playback(&self, prayer: Prayer, time: chrono::NaiveTime, default: f32) -> (f32, bool) {
+        let profile = self
+            .active
+            .as_ref()
+            .and_then(|name| self.profiles.iter().find(|profile| &profile.name == name));
+        let mut volume = profile
+            .and_then(|profile| profile.prayers.get(&prayer).copied().or(profile.volume))
+            .unwrap_or(default);
+
+        let mut chime = false;
+        for quiet_hours in self.quiet_hours.iter().filter(|q| q.contains(time)) {
+            match quiet_hours.action {
+                QuietAction::Chime => chime = true,
+                QuietAction::Volume(max) => volume = volume.min(max),
+            }
+        }
+        (volume, chime)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let volumes = self.profiles.iter().flat_map(|profile| {
+            profile
+                .volume
+                .into_iter()
+                .chain(profile.prayers.values().copied())
+        });
+        let quiet_volumes = self.quiet_hours.iter().filter_map(|q| match q.action {
+            QuietAction::Volume(volume) => Some(volume),
+            QuietAction::Chime => None,
+        });
+        if volumes
+            .chain(quiet_volumes)
+            .any(|volume| !(0.0..=MAX_VOLUME).contains(&volume))
+        {
+            return Err(format!("volumes must be between 0 and {}", MAX_VOLUME));
+        }
+        match &self.active {
+            Some(name) if !self.profiles.iter().any(|profile| &profile.name == name) => {
+                Err(format!("unknown volume profile: {}", name))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// gradual start and stop of playback, in seconds
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct FadeSettings {
+    pub fade_in: f32,
+    // on stop/halt
+    pub fade_out: f32,
+    // fade in per prayer e.g. a longer one for fajr
+    pub prayers: HashMap<Prayer, f32>,
+}
+
+impl FadeSettings {
+    pub fn fade_in(&self, prayer: Prayer) -> std::time::Duration {
+        let seconds = self.prayers.get(&prayer).copied().unwrap_or(self.fade_in);
+        std::time::Duration::from_secs_f32(seconds.max(0.0))
+    }
+
+    pub fn fade_out(&self) -> std::time::Duration {
+        std::time::Duration::from_secs_f32(self.fade_out.max(0.0))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let mut seconds = [self.fade_in, self.fade_out]
+            .into_iter()
+            .chain(self.prayers.values().copied());
+        if seconds.any(|seconds| !(0.0..=MAX_FADE).contains(&seconds)) {
+            return Err(format!("fades must be between 0 and {} seconds", MAX_FADE));
+        }
+        Ok(())
+    }
+}
+
+// longest fade, in seconds
+const MAX_FADE: f32 = 60.0;
+
+// loudest sink volume
+pub const MAX_VOLUME: f32 = 15.0;
+
+// volume percentage (0 - 100) as sink level
+pub fn percent_to_level(percent: u8) -> f32 {
+    percent.min(100) as f32 / 100.0 * MAX_VOLUME
+}
+
+pub fn level_to_percent(level: f32) -> u8 {
+    (level / MAX_VOLUME * 100.0).round().clamp(0.0, 100.0) as u8
+}
+
+// names of sounds in the library; embedded defaults are played if unset
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SoundSettings {
+    // adhan recitation per prayer, or alert for other events (sunrise, imsak etc.)
+    pub prayers: HashMap<Prayer, String>,
+    pub chime: Option<String>,
+    // reminder per prayer e.g. a spoken announcement; the chime is played if unset
+    pub reminders: HashMap<Prayer, String>,
+    pub ramadan: HashMap<RamadanEvent, String>,
+    // jumu'ah adhan; the dhuhr adhan is played if unset
+    pub jumuah: Option<String>,
+    // "leave for jumu'ah" reminder; the chime is played if unset
+    pub leave_for_jumuah: Option<String>,
+}
+
+// Settings held in memory; optionally persisted to a JSON file on every update. Of the location,
+// only fields that differ from the defaults (command line / config file) are saved, so those keep
+// applying to fields not changed via the api.
+pub struct SettingsStore {
+    path: Option<PathBuf>,
+    defaults: Params,
+    settings: RwLock<Settings>,
+}
+
+impl SettingsStore {
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            path: None,
+            defaults: settings.params.clone(),
+            settings: RwLock::new(settings),
+        }
+    }
+
+    // load settings from path, saved location fields layered over those of `defaults`
+    pub fn open(path: impl Into<PathBuf>, defaults: Settings) -> Result<Self, String> {
+        let path = path.into();
+        let settings = match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                let mut saved: serde_json::Value = serde_json::from_str(&contents)
+                    .map_err(|e| format!("Error parsing settings file {:?}: {:?}", path, e))?;
+                let mut params = serde_json::to_value(&defaults.params).unwrap();
+                if let (Some(params), Some(serde_json::Value::Object(changed))) =
+                    (params.as_object_mut(), saved.get("params"))
+                {
+                    params.extend(changed.clone());
+                }
+                saved["params"] = params;
+                serde_json::from_value(saved)
+                    .map_err(|e| format!("Error parsing settings file {:?}: {:?}", path, e))?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => defaults.clone(),
+            Err(e) => return Err(format!("Error reading settings file {:?}: {:?}", path, e)),
+        };
+        Ok(Self {
+            path: Some(path),
+            defaults: defaults.params,
+            settings: RwLock::new(settings),
+        })
+    }
+
+    pub fn get(&self) -> Settings {
+        self.settings.read().unwrap().clone()
+    }
+
+    pub fn update(&self, f: impl FnOnce(&mut Settings)) {
+        let mut settings = self.settings.write().unwrap();
+        f(&mut settings);
+
+        if let Some(path) = &self.path {
+            if let Err(e) = crate::data::write_json(path, &self.saved(&settings)) {
+                tracing::error!("Error writing settings file {:?}: {:?}", path, e);
+            }
+        }
+    }
+
+    // settings as saved: location fields equal to the defaults are left out
+    fn saved(&self, settings: &Settings) -> serde_json::Value {
+        let mut saved = serde_json::to_value(settings).unwrap();
+        let defaults = serde_json::to_value(&self.defaults).unwrap();
+        if let Some(serde_json::Value::Object(params)) = saved.get_mut("params") {
+            params.retain(|field, value| defaults.get(field) != Some(value));
+        }
+        saved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jumuah_times() {
+        let dhuhr = chrono::NaiveTime::from_hms_opt(13, 5, 0).unwrap();
+        let thursday = chrono::NaiveDate::from_ymd_opt(2022, 12, 29).unwrap();
+        let friday = chrono::NaiveDate::from_ymd_opt(2022, 12, 30).unwrap();
+        let mut jumuah = JumuahSettings {
+            time: IqamahTime::Fixed(chrono::NaiveTime::from_hms_opt(13, 30, 0).unwrap()),
+            leave_reminder: Some(20),
+            ..JumuahSettings::default()
+        };
+        assert_eq!(jumuah.times(friday, dhuhr), None);
+
+        jumuah.enabled = true;
+        assert_eq!(jumuah.times(thursday, dhuhr), None);
+        assert_eq!(
+            jumuah.times(friday, dhuhr),
+            Some((
+                chrono::NaiveTime::from_hms_opt(13, 30, 0).unwrap(),
+                Some(chrono::NaiveTime::from_hms_opt(13, 10, 0).unwrap())
+            ))
+        );
+
+        // times that would fall on another day are skipped
+        let early = chrono::NaiveTime::from_hms_opt(0, 10, 0).unwrap();
+        jumuah.time = IqamahTime::Fixed(early);
+        assert_eq!(jumuah.times(friday, dhuhr), Some((early, None)));
+        jumuah.time = IqamahTime::Offset(660);
+        assert_eq!(jumuah.times(friday, dhuhr), None);
+
+        assert!(jumuah.validate().is_err());
+        jumuah.time = IqamahTime::Offset(0);
+        assert!(jumuah.validate().is_ok());
+        jumuah.leave_reminder = Some(0);
+        assert!(jumuah.validate().is_err());
+    }
+
+    #[test]
+    fn test_volume_playback() {
+        let volume: VolumeSettings = serde_json::from_str(
+            r#"{
+                "active": "kids",
+                "profiles": [{"name": "kids", "volume": 4.0, "prayers": {"Fajr": 2.0, "Dhuhr": 8.0}}],
+                "quiet_hours": [
+                    {"start": "21:00:00", "end": "06:00:00", "action": "chime"},
+                    {"start": "13:00:00", "end": "14:00:00", "action": {"volume": 3.0}}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert!(volume.validate().is_ok());
+        let time = |h| chrono::NaiveTime::from_hms_opt(h, 0, 0).unwrap();
+
+        assert_eq!(volume.playback(Prayer::Fajr, time(7), 5.0), (2.0, false));
+        assert_eq!(volume.playback(Prayer::Asr, time(16), 5.0), (4.0, false));
+        // quiet hours spanning midnight, and a volume cap
+        assert_eq!(volume.playback(Prayer::Fajr, time(5), 5.0), (2.0, true));
+        assert_eq!(volume.playback(Prayer::Dhuhr, time(13), 5.0), (3.0, false));
+
+        let none = VolumeSettings::default();
+        assert_eq!(none.playback(Prayer::Isha, time(22), 5.0), (5.0, false));
+    }
+
+    #[test]
+    fn test_saved_location() {
+        let path =
+            std::env::temp_dir().join(format!("prayer-alarm-settings-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let defaults = Settings::default();
+
+        // an unrelated change does not pin the default location
+        let store = SettingsStore::open(&path, defaults.clone()).unwrap();
+        store.update(|settings| settings.volume_level = Some(3.0));
+        let mut london = Params::new("London", "UK");
+        london.coordinates = Some((51.5, -0.12));
+        let store = SettingsStore::open(
+            &path,
+            Settings {
+                params: london.clone(),
+                ..defaults.clone()
+            },
+        )
+        .unwrap();
+        assert_eq!(store.get().params, london);
+        assert_eq!(store.get().volume_level, Some(3.0));
+
+        // fields changed via the api are kept over new defaults; the others follow them
+        store.update(|settings| settings.params.method = 2);
+        london.hijri_adjustment = 1;
+        let store = SettingsStore::open(
+            &path,
+            Settings {
+                params: london.clone(),
+                ..defaults
+            },
+        )
+        .unwrap();
+        assert_eq!(store.get().params.method, 2);
+        assert_eq!(store.get().params.hijri_adjustment, 1);
+        assert_eq!(store.get().params.city, "London");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_fade() {
+        let fade = FadeSettings {
+            fade_in: 2.0,
+            prayers: HashMap::from([(Prayer::Fajr, 15.0)]),
+            ..FadeSettings::default()
+        };
+        assert_eq!(fade.fade_in(Prayer::Fajr).as_secs(), 15);
+        assert_eq!(fade.fade_in(Prayer::Dhuhr).as_secs(), 2);
+        assert!(fade.fade_out().is_zero());
+        assert!(FadeSettings {
+            fade_out: -1.0,
+            ..fade
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_volume_percent() {
+        assert_eq!(percent_to_level(0), 0.0);
+        assert_eq!(percent_to_level(100), MAX_VOLUME);
+        assert_eq!(percent_to_level(200), MAX_VOLUME);
+        assert_eq!(level_to_percent(5.0), 33);
+        assert_eq!(level_to_percent(percent_to_level(40)), 40);
+    }
+
+    #[test]
+    fn test_settings_defaults() {
+        let settings: Settings = serde_json::from_str(r#"{"jumuah": {"enabled": true}}"#).unwrap();
+        assert_eq!(settings.jumuah.time, IqamahTime::Offset(0));
+        assert_eq!(settings.params, Params::default());
+    }
+}
@@ -0,0 +1,161 @@
+// Library of adhan recitations and chimes.
+//
+// Uploaded files live in a directory on disk; the files compiled into the binary (`mp3/`) are always
+// available as fallbacks and cannot be deleted.
+use serde::Serialize;
+use std::path::PathBuf;
+
+#[derive(rust_embed::RustEmbed)]
+#[folder = "mp3/"]
+struct Assets;
+
+const EXTENSIONS: [&str; 3] = ["mp3", "ogg", "wav"];
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Sound {
+    pub name: String,
+    pub embedded: bool,
+}
+
+pub struct SoundLibrary {
+    dir: PathBuf,
+}
+
+impl SoundLibrary {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    // uploaded sounds followed by embedded ones
+    pub fn list(&self) -> Vec<Sound> {
+        let mut uploaded: Vec<Sound> = std::fs::read_dir(&self.dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                    .filter(|name| is_valid_name(name))
+                    .map(|name| Sound {
+                        name,
+                        embedded: false,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        uploaded.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let embedded = Assets::iter().map(|name| Sound {
+            name: name.into_owned(),
+            embedded: true,
+        });
+        uploaded.into_iter().chain(embedded).collect()
+    }
+
+    // uploaded sound of name, or the embedded one
+    pub fn load(&self, name: &str) -> Option<Vec<u8>> {
+        if !is_valid_name(name) {
+            return None;
+        }
+        std::fs::read(self.dir.join(name))
+            .ok()
+            .or_else(|| Assets::get(name).map(|file| file.data.into_owned()))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        is_valid_name(name) && (self.dir.join(name).is_file() || Assets::get(name).is_some())
+    }
+
+    pub fn save(&self, name: &str, data: &[u8]) -> Result<(), String> {
+        if !is_valid_name(name) {
+            return Err(format!(
+                "invalid sound name {:?}; expected letters, digits, '-', '_' and a {} extension",
+                name,
+                EXTENSIONS.join("/")
+            ));
+        }
+        if !has_audio_header(data) {
+            return Err("unsupported audio file: expected MP3, OGG or WAV data".to_owned());
+        }
+
+        std::fs::create_dir_all(&self.dir)
+            .and_then(|_| std::fs::write(self.dir.join(name), data))
+            .map_err(|e| format!("Error saving sound {:?}: {:?}", name, e))
+    }
+
+    // delete uploaded sound; returns false if there is no such upload
+    pub fn delete(&self, name: &str) -> Result<bool, String> {
+        if !is_valid_name(name) || !self.dir.join(name).is_file() {
+            return Ok(false);
+        }
+        std::fs::remove_file(self.dir.join(name))
+            .map(|_| true)
+            .map_err(|e| format!("Error deleting sound {:?}: {:?}", name, e))
+    }
+}
+
+// plain file names only (no paths), with a supported extension
+fn is_valid_name(name: &str) -> bool {
+    match name.rsplit_once('.') {
+        Some((stem, extension)) => {
+            !stem.is_empty()
+                && stem
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                && EXTENSIONS.contains(&extension.to_lowercase().as_str())
+        }
+        None => false,
+    }
+}
+
+// format check by magic bytes; files are only decoded when played
+fn has_audio_header(data: &[u8]) -> bool {
+    match data {
+        [b'I', b'D', b'3', ..] | [b'O', b'g', b'g', b'S', ..] => true,
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => true,
+        // mp3 frame sync
+        [0xFF, second, ..] => second & 0xE0 == 0xE0,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_names() {
+        assert!(is_valid_name("adhan-makkah_1.mp3"));
+        assert!(is_valid_name("chime.OGG"));
+        assert!(!is_valid_name("../settings.json"));
+        assert!(!is_valid_name("a/b.mp3"));
+        assert!(!is_valid_name(".mp3"));
+        assert!(!is_valid_name("adhan.flac"));
+    }
+
+    #[test]
+    fn test_upload_list_delete() {
+        let dir = std::env::temp_dir().join(format!("prayer-alarm-sounds-{}", std::process::id()));
+        let library = SoundLibrary::new(&dir);
+        let data = Assets::get("test.mp3").unwrap().data.into_owned();
+
+        assert!(library.save("not-audio.mp3", b"hello").is_err());
+        library.save("my-adhan.mp3", &data).unwrap();
+        assert_eq!(
+            library.list()[0],
+            Sound {
+                name: "my-adhan.mp3".to_owned(),
+                embedded: false
+            }
+        );
+        assert_eq!(library.load("my-adhan.mp3"), Some(data));
+
+        // embedded sounds are listed but cannot be deleted
+        assert!(library
+            .list()
+            .iter()
+            .any(|s| s.name == "test.mp3" && s.embedded));
+        assert!(!library.delete("test.mp3").unwrap());
+
+        assert!(library.delete("my-adhan.mp3").unwrap());
+        assert!(!library.contains("my-adhan.mp3"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,313 @@
+// Live state of the player and scheduler, shared with the http api; changes are broadcast as
+// notifications (streamed to clients via `/events`).
+use chrono::Datelike;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+use crate::error::ErrorReport;
+use crate::settings;
+use crate::structs::Prayer;
+use crate::timezone::Zone;
+
+// notifications kept for slow subscribers before they start missing some
+const CHANNEL_CAPACITY: usize = 64;
+
+#[derive(Debug, Copy, Clone, Default, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaybackState {
+    #[default]
+    Idle,
+    Playing,
+    Paused,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PlayerStatus {
+    pub state: PlaybackState,
+    // sound playing (the first of the queue)
+    pub track: Option<String>,
+    pub prayer: Option<Prayer>,
+    // e.g. "adhan", "iqamah"
+    pub event: Option<String>,
+    // sink level
+    pub volume: f32,
+    // seconds since playback started, excluding pauses
+    pub position: f64,
+    // instant of position 0 while playing
+    #[serde(skip)]
+    started: Option<Instant>,
+}
+
+impl PlayerStatus {
+    pub fn play(&mut self, track: String, prayer: Prayer, event: String, volume: f32) {
+        *self = Self {
+            state: PlaybackState::Playing,
+            track: Some(track),
+            prayer: Some(prayer),
+            event: Some(event),
+            volume,
+            position: 0.0,
+            started: Some(Instant::now()),
+        };
+    }
+
+    pub fn pause(&mut self) {
+        if let (PlaybackState::Playing, Some(started)) = (self.state, self.started) {
+            self.state = PlaybackState::Paused;
+            self.position = started.elapsed().as_secs_f64();
+            self.started = None;
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if self.state == PlaybackState::Paused {
+            self.state = PlaybackState::Playing;
+            self.started = Instant::now().checked_sub(Duration::from_secs_f64(self.position));
+        }
+    }
+
+    // back to idle, keeping the volume
+    pub fn stop(&mut self) {
+        *self = Self {
+            volume: self.volume,
+            ..Self::default()
+        };
+    }
+}
+
+// event the scheduler is waiting for
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct NextEvent {
+    pub datetime: chrono::DateTime<chrono::FixedOffset>,
+    pub prayer: Prayer,
+    pub event: String,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Notification {
+    PrayerFired {
+        prayer: Prayer,
+        event: String,
+    },
+    PlaybackStarted {
+        track: Option<String>,
+        prayer: Option<Prayer>,
+        event: Option<String>,
+    },
+    PlaybackPaused,
+    PlaybackResumed,
+    PlaybackStopped,
+    // percent
+    VolumeChanged {
+        volume: u8,
+    },
+    TimingsRefreshed,
+    ConfigChanged {
+        section: String,
+    },
+}
+
+#[derive(Debug)]
+pub struct Status {
+    player: RwLock<PlayerStatus>,
+    next: RwLock<Vec<NextEvent>>,
+    // last failure to fetch prayer timings
+    error: RwLock<Option<ErrorReport>>,
+    // source of the timings in use while the provider is failing e.g. "cache", by month
+    fallback: RwLock<BTreeMap<chrono::NaiveDate, &'static str>>,
+    // zone the scheduler runs in
+    timezone: RwLock<Zone>,
+    notifications: broadcast::Sender<Notification>,
+}
+
+impl Status {
+    pub fn new(volume: f32) -> Self {
+        Self {
+            player: RwLock::new(PlayerStatus {
+                volume,
+                ..PlayerStatus::default()
+            }),
+            next: RwLock::new(vec![]),
+            error: RwLock::new(None),
+            fallback: RwLock::new(BTreeMap::new()),
+            timezone: RwLock::new(Zone::system()),
+            notifications: broadcast::channel(CHANNEL_CAPACITY).0,
+        }
+    }
+
+    // no-op without subscribers
+    pub fn publish(&self, notification: Notification) {
+        let _ = self.notifications.send(notification);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Notification> {
+        self.notifications.subscribe()
+    }
+
+    // player status with the position at the time of the call
+    pub fn player(&self) -> PlayerStatus {
+        let mut player = self.player.read().unwrap().clone();
+        if let Some(started) = player.started {
+            player.position = started.elapsed().as_secs_f64();
+        }
+        player
+    }
+
+    // publishes playback and volume changes made by f
+    pub fn update_player(&self, f: impl FnOnce(&mut PlayerStatus)) {
+        let (before, after) = {
+            let mut player = self.player.write().unwrap();
+            let before = player.clone();
+            f(&mut player);
+            (before, player.clone())
+        };
+
+        match (before.state, after.state) {
+            (PlaybackState::Idle, PlaybackState::Playing) => {
+                self.publish(Notification::PlaybackStarted {
+                    track: after.track,
+                    prayer: after.prayer,
+                    event: after.event,
+                })
+            }
+            (PlaybackState::Playing, PlaybackState::Paused) => {
+                self.publish(Notification::PlaybackPaused)
+            }
+            (PlaybackState::Paused, PlaybackState::Playing) => {
+                self.publish(Notification::PlaybackResumed)
+            }
+            (PlaybackState::Playing | PlaybackState::Paused, PlaybackState::Idle) => {
+                self.publish(Notification::PlaybackStopped)
+            }
+            _ => {}
+        }
+        if before.volume != after.volume {
+            self.publish(Notification::VolumeChanged {
+                volume: settings::level_to_percent(after.volume),
+            });
+        }
+    }
+
+    pub fn next(&self) -> Vec<NextEvent> {
+        self.next.read().unwrap().clone()
+    }
+
+    pub fn set_next(&self, next: Vec<NextEvent>) {
+        *self.next.write().unwrap() = next;
+    }
+
+    pub fn error(&self) -> Option<ErrorReport> {
+        self.error.read().unwrap().clone()
+    }
+
+    pub fn set_error(&self, error: Option<ErrorReport>) {
+        *self.error.write().unwrap() = error;
+    }
+
+    pub fn timezone(&self) -> Zone {
+        *self.timezone.read().unwrap()
+    }
+
+    pub fn set_timezone(&self, timezone: Zone) {
+        *self.timezone.write().unwrap() = timezone;
+    }
+
+    // fallback of the earliest month not fetched from the provider
+    pub fn fallback(&self) -> Option<&'static str> {
+        self.fallback.read().unwrap().values().next().copied()
+    }
+
+    pub fn set_fallback(&self, month: chrono::NaiveDate, fallback: Option<&'static str>) {
+        let month = month.with_day(1).unwrap();
+        let mut fallbacks = self.fallback.write().unwrap();
+        match fallback {
+            Some(fallback) => fallbacks.insert(month, fallback),
+            None => fallbacks.remove(&month),
+        };
+    }
+
+    // forget months that have passed
+    pub fn clear_fallback_before(&self, date: chrono::NaiveDate) {
+        let month = date.with_day(1).unwrap();
+        self.fallback.write().unwrap().retain(|m, _| *m >= month);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_player_status() {
+        let status = Status::new(5.0);
+        assert_eq!(status.player().state, PlaybackState::Idle);
+
+        status.update_player(|player| {
+            player.play(
+                "adhan.mp3".to_owned(),
+                Prayer::Fajr,
+                "adhan".to_owned(),
+                3.0,
+            )
+        });
+        std::thread::sleep(Duration::from_millis(20));
+        status.update_player(PlayerStatus::pause);
+        let paused = status.player();
+        assert_eq!(paused.state, PlaybackState::Paused);
+        assert!(paused.position >= 0.02);
+
+        // position does not advance while paused
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(status.player().position, paused.position);
+
+        status.update_player(PlayerStatus::resume);
+        assert!(status.player().position >= paused.position);
+
+        status.update_player(PlayerStatus::stop);
+        let stopped = status.player();
+        assert_eq!(stopped.state, PlaybackState::Idle);
+        assert_eq!(stopped.track, None);
+        assert_eq!(stopped.volume, 3.0);
+    }
+
+    #[test]
+    fn test_notifications() {
+        let status = Status::new(5.0);
+        let mut notifications = status.subscribe();
+
+        status.update_player(|player| {
+            player.play("adhan.mp3".to_owned(), Prayer::Asr, "adhan".to_owned(), 5.0)
+        });
+        status.update_player(|player| player.volume = 15.0);
+        status.update_player(|player| player.volume = 15.0);
+        status.update_player(PlayerStatus::pause);
+        status.update_player(PlayerStatus::pause);
+        status.update_player(PlayerStatus::stop);
+
+        assert_eq!(
+            notifications.try_recv().unwrap(),
+            Notification::PlaybackStarted {
+                track: Some("adhan.mp3".to_owned()),
+                prayer: Some(Prayer::Asr),
+                event: Some("adhan".to_owned()),
+            }
+        );
+        assert_eq!(
+            notifications.try_recv().unwrap(),
+            Notification::VolumeChanged { volume: 100 }
+        );
+        assert_eq!(
+            notifications.try_recv().unwrap(),
+            Notification::PlaybackPaused
+        );
+        assert_eq!(
+            notifications.try_recv().unwrap(),
+            Notification::PlaybackStopped
+        );
+        assert!(notifications.try_recv().is_err());
+    }
+}
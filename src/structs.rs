@@ -2,6 +2,10 @@ use chrono::Datelike;
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap};
 
+use crate::calc::School;
+use crate::error::AdhanError;
+use crate::settings::{RamadanMode, RamadanSettings};
+
 // {
 //   "code": 200,
 //   "status": "OK",
@@ -112,20 +116,21 @@ pub mod api {
         pub meta: Meta,
     }
 
-    #[derive(Debug, Clone, Serialize, Deserialize)]
-    #[serde(rename_all = "PascalCase")]
+    // missing timings are left empty
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+    #[serde(rename_all = "PascalCase", default)]
     pub struct Timings {
         pub fajr: String,
-        // pub sunrise: String,
+        pub sunrise: String,
         pub dhuhr: String,
         pub asr: String,
-        // pub sunset: String,
+        pub sunset: String,
         pub maghrib: String,
         pub isha: String,
-        // pub imsak: String,
-        // pub midnight: String,
-        // pub firstthird: String,
-        // pub lastthird: String,
+        pub imsak: String,
+        pub midnight: String,
+        pub firstthird: String,
+        pub lastthird: String,
     }
 
     #[derive(Debug, Clone, Deserialize)]
@@ -133,6 +138,7 @@ pub mod api {
         pub readable: String,
         pub timestamp: String,
         pub gregorian: Gregorian,
+        pub hijri: Hijri,
     }
 
     #[derive(Debug, Clone, Deserialize)]
@@ -146,14 +152,43 @@ pub mod api {
     }
 
     #[derive(Debug, Clone, Deserialize)]
+    pub struct Hijri {
+        pub date: String,
+        // only the date is needed; the rest may be missing from the response
+        #[serde(default)]
+        pub format: String,
+        pub day: String,
+        #[serde(default)]
+        pub weekday: Weekday,
+        pub month: Month,
+        pub year: String,
+        #[serde(default)]
+        pub designation: Designation,
+        #[serde(default)]
+        pub holidays: Vec<String>,
+    }
+
+    #[derive(Debug, Clone, Default, Deserialize)]
+    pub struct Designation {
+        pub abbreviated: String,
+        pub expanded: String,
+    }
+
+    #[derive(Debug, Clone, Default, Deserialize)]
     pub struct Weekday {
         pub en: String,
+        // hijri only
+        #[serde(default)]
+        pub ar: String,
     }
 
     #[derive(Debug, Clone, Deserialize)]
     pub struct Month {
         pub number: u8,
         pub en: String,
+        // hijri only
+        #[serde(default)]
+        pub ar: String,
     }
 
     #[derive(Debug, Clone, Deserialize)]
@@ -164,38 +199,66 @@ pub mod api {
         pub offset: Offset,
     }
 
+    // tune minutes echoed back; negative if timings were moved earlier
     #[derive(Debug, Clone, Deserialize)]
     #[serde(rename_all = "PascalCase")]
     pub struct Offset {
-        pub imsak: u8,
-        pub fajr: u8,
-        pub sunrise: u8,
-        pub dhuhr: u8,
-        pub asr: u8,
-        pub maghrib: u8,
-        pub sunset: u8,
-        pub isha: u8,
-        pub midnight: u8,
+        pub imsak: i16,
+        pub fajr: i16,
+        pub sunrise: i16,
+        pub dhuhr: i16,
+        pub asr: i16,
+        pub maghrib: i16,
+        pub sunset: i16,
+        pub isha: i16,
+        pub midnight: i16,
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct Params<'a> {
-    pub city: &'a str,
-    pub country: &'a str,
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Params {
+    pub city: String,
+    pub country: String,
+    #[serde(default)]
+    pub coordinates: Option<(f64, f64)>, // latitude, longitude (takes precedence over city/country)
     pub method: u8,
+    #[serde(default)]
+    pub school: School,
+    #[serde(skip, default = "today")]
     pub date: chrono::NaiveDate,
+    #[serde(default)]
     pub offsets: (i8, i8, i8, i8, i8), // fajr, dhuhr, asr, maghrib, isha
+    #[serde(default)]
+    pub hijri_adjustment: i8, // days (-2..=2) to match local moon sighting
+    // IANA zone of the location e.g. "Pacific/Auckland"; the api's zone (or the system's) if unset
+    #[serde(default)]
+    pub timezone: Option<chrono_tz::Tz>,
+}
+
+fn today() -> chrono::NaiveDate {
+    chrono::Local::now().date_naive()
 }
 
-impl<'a> Params<'a> {
-    pub fn new(city: &'a str, country: &'a str) -> Self {
+impl Default for Params {
+    fn default() -> Self {
+        let mut params = Self::new("Auckland", "NewZealand");
+        params.coordinates = Some((-36.8484597, 174.7633315));
+        params
+    }
+}
+
+impl Params {
+    pub fn new(city: impl Into<String>, country: impl Into<String>) -> Self {
         Self {
-            city,
-            country,
+            city: city.into(),
+            country: country.into(),
+            coordinates: None,
             method: 3,
-            date: chrono::Local::today().naive_local(),
+            school: School::default(),
+            date: today(),
             offsets: (0, 0, 0, 0, 0),
+            hijri_adjustment: 0,
+            timezone: None,
         }
     }
     pub fn to_prayer_timings_url(&self) -> String {
@@ -203,38 +266,63 @@ impl<'a> Params<'a> {
         let (fajr, dhuhr, asr, maghrib, isha) = self.offsets;
         let tune_params = format!("0,{},0,{},{},{},0,{}", fajr, dhuhr, asr, maghrib, isha);
 
+        let location = match self.coordinates {
+            Some((latitude, longitude)) => {
+                format!("calendar?latitude={}&longitude={}", latitude, longitude)
+            }
+            None => format!("calendarByCity?city={}&country={}", self.city, self.country),
+        };
+
         format!(
-            "http://api.aladhan.com/v1/calendarByCity?city={}&country={}&method={}&month={}&year={}&tune={}",
-            self.city,
-            self.country,
+            "http://api.aladhan.com/v1/{}&method={}&school={}&month={}&year={}&tune={}&adjustment={}",
+            location,
             self.method,
+            self.school.id(),
             self.date.month(),
             self.date.year(),
             tune_params,
+            self.hijri_adjustment,
         )
     }
 }
 
-#[derive(Debug, Copy, Clone, serde::Serialize, Eq, PartialEq, Hash)]
+// the five prayers plus other times of the day that can be scheduled as events
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq, Hash)]
 pub enum Prayer {
+    Imsak,
     Fajr,
+    // end of fajr time
+    Sunrise,
     Dhuhr,
     Asr,
     Maghrib,
     Isha,
+    // midnight and last third of the night (tahajjud) fall after 00:00 and are scheduled on the
+    // morning of the day they are listed under
+    Midnight,
+    LastThird,
 }
 
-impl Prayer {
-    pub fn from_str(p: impl Into<String>) -> Option<Self> {
-        match p.into().to_lowercase().as_str() {
-            "fajr" => Some(Self::Fajr),
-            "dhuhr" => Some(Self::Dhuhr),
-            "asr" => Some(Self::Asr),
-            "maghrib" => Some(Self::Maghrib),
-            "isha" => Some(Self::Isha),
-            _ => None,
+impl std::str::FromStr for Prayer {
+    type Err = String;
+
+    fn from_str(p: &str) -> Result<Self, Self::Err> {
+        match p.to_lowercase().as_str() {
+            "fajr" => Ok(Self::Fajr),
+            "dhuhr" => Ok(Self::Dhuhr),
+            "asr" => Ok(Self::Asr),
+            "maghrib" => Ok(Self::Maghrib),
+            "isha" => Ok(Self::Isha),
+            "imsak" => Ok(Self::Imsak),
+            "sunrise" => Ok(Self::Sunrise),
+            "midnight" => Ok(Self::Midnight),
+            "lastthird" => Ok(Self::LastThird),
+            _ => Err(format!("invalid prayer name: {}", p)),
         }
     }
+}
+
+impl Prayer {
     pub fn name(&self) -> String {
         match self {
             Self::Fajr => "Fajr".to_string(),
@@ -242,8 +330,20 @@ impl Prayer {
             Self::Asr => String::from("Asr"),
             Self::Maghrib => String::from("Maghrib"),
             Self::Isha => String::from("Isha"),
+            Self::Imsak => String::from("Imsak"),
+            Self::Sunrise => String::from("Sunrise"),
+            Self::Midnight => String::from("Midnight"),
+            Self::LastThird => String::from("LastThird"),
         }
     }
+
+    // one of the five daily prayers (as opposed to another time of the day)
+    pub fn is_prayer(&self) -> bool {
+        matches!(
+            self,
+            Self::Fajr | Self::Dhuhr | Self::Asr | Self::Maghrib | Self::Isha
+        )
+    }
 }
 
 impl From<&str> for Prayer {
@@ -254,17 +354,281 @@ impl From<&str> for Prayer {
             "asr" => Self::Asr,
             "maghrib" => Self::Maghrib,
             "isha" => Self::Isha,
+            "imsak" => Self::Imsak,
+            "sunrise" => Self::Sunrise,
+            "midnight" => Self::Midnight,
+            "lastthird" => Self::LastThird,
             _ => panic!("invalid prayer name"),
         }
     }
 }
 
-#[derive(Debug, Clone, serde::Serialize)]
+// iqamah (congregation) time of a prayer
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum IqamahTime {
+    // fixed clock time e.g. `{"fixed": "13:30:00"}`
+    Fixed(chrono::NaiveTime),
+    // minutes after the adhan e.g. `{"offset": 15}`
+    Offset(i64),
+}
+
+// longest wait between adhan and iqamah, in minutes
+const MAX_IQAMAH_OFFSET: i64 = 180;
+
+impl IqamahTime {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            IqamahTime::Offset(minutes) if !(0..=MAX_IQAMAH_OFFSET).contains(minutes) => {
+                Err(format!(
+                    "offsets must be between 0 and {} minutes",
+                    MAX_IQAMAH_OFFSET
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IqamahSchedule {
+    pub time: IqamahTime,
+    // per weekday overrides e.g. `{"Sat": {"fixed": "13:00:00"}}`
+    #[serde(default)]
+    pub weekdays: HashMap<chrono::Weekday, IqamahTime>,
+}
+
+impl IqamahSchedule {
+    // none if an offset would move the iqamah to another day
+    pub fn iqamah_time(
+        &self,
+        date: chrono::NaiveDate,
+        adhan: chrono::NaiveTime,
+    ) -> Option<chrono::NaiveTime> {
+        match self.weekdays.get(&date.weekday()).unwrap_or(&self.time) {
+            IqamahTime::Fixed(time) => Some(*time),
+            IqamahTime::Offset(minutes) => {
+                match adhan.overflowing_add_signed(chrono::Duration::minutes(*minutes)) {
+                    (iqamah, 0) => Some(iqamah),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.weekdays
+            .values()
+            .chain([&self.time])
+            .try_for_each(IqamahTime::validate)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HijriDate {
+    pub day: u8,
+    pub month: u8,
+    pub year: u16,
+    #[serde(default)]
+    pub month_name: String,
+}
+
+impl HijriDate {
+    pub fn is_ramadan(&self) -> bool {
+        self.month == 9
+    }
+}
+
+impl TryFrom<&api::Hijri> for HijriDate {
+    type Error = String;
+
+    fn try_from(hijri: &api::Hijri) -> Result<Self, Self::Error> {
+        let error = |e| format!("invalid hijri date {:?}: {:?}", hijri.date, e);
+        Ok(Self {
+            day: hijri.day.parse().map_err(error)?,
+            month: hijri.month.number,
+            year: hijri.year.parse().map_err(error)?,
+            month_name: hijri.month.en.to_owned(),
+        })
+    }
+}
+
+// alarms played during ramadan
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum RamadanEvent {
+    // wake-up alarm before imsak/fajr
+    Suhoor,
+    // at maghrib
+    Iftar,
+    // after isha
+    Taraweeh,
+}
+
+impl RamadanEvent {
+    // prayer the event belongs to
+    pub fn prayer(&self) -> Prayer {
+        match self {
+            Self::Suhoor => Prayer::Fajr,
+            Self::Iftar => Prayer::Maghrib,
+            Self::Taraweeh => Prayer::Isha,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PrayerTime {
     pub date: String,
     pub timestamp: u32,
     pub timings: BTreeMap<String, Prayer>,
+    pub iqamah: BTreeMap<String, Prayer>,
     pub play_adhan: HashMap<Prayer, bool>,
+    pub play_iqamah: HashMap<Prayer, bool>,
+    // reminder times before the adhan, "HH:MM:SS" -> prayer
+    #[serde(default)]
+    pub reminders: BTreeMap<String, Prayer>,
+    #[serde(default = "default_toggles")]
+    pub play_reminder: HashMap<Prayer, bool>,
+    // unknown if the provider has no hijri calendar
+    #[serde(default)]
+    pub hijri: Option<HijriDate>,
+    // islamic holidays e.g. "Eid-ul-Fitr"
+    #[serde(default)]
+    pub holidays: Vec<String>,
+    // ramadan alarm times, "HH:MM:SS" -> event
+    #[serde(default)]
+    pub ramadan: BTreeMap<String, RamadanEvent>,
+    #[serde(default = "default_ramadan_toggles")]
+    pub play_ramadan: HashMap<RamadanEvent, bool>,
+    // zone the timings are in; the system's if unknown
+    #[serde(default)]
+    pub timezone: Option<chrono_tz::Tz>,
+}
+
+fn default_ramadan_toggles() -> HashMap<RamadanEvent, bool> {
+    HashMap::from([
+        (RamadanEvent::Suhoor, true),
+        (RamadanEvent::Iftar, true),
+        (RamadanEvent::Taraweeh, true),
+    ])
+}
+
+// prayers enabled, other events disabled
+fn default_toggles() -> HashMap<Prayer, bool> {
+    HashMap::from([
+        (Prayer::Imsak, false),
+        (Prayer::Fajr, true),
+        (Prayer::Sunrise, false),
+        (Prayer::Dhuhr, true),
+        (Prayer::Asr, true),
+        (Prayer::Maghrib, true),
+        (Prayer::Isha, true),
+        (Prayer::Midnight, false),
+        (Prayer::LastThird, false),
+    ])
+}
+
+impl PrayerTime {
+    // prayer time for date with all adhans enabled (other events disabled); timings map
+    // "HH:MM:SS" -> prayer
+    pub fn new(date: chrono::NaiveDate, timestamp: u32, timings: BTreeMap<String, Prayer>) -> Self {
+        PrayerTime {
+            date: date.to_string(),
+            timestamp,
+            timings,
+            iqamah: BTreeMap::new(),
+            play_adhan: default_toggles(),
+            play_iqamah: default_toggles(),
+            reminders: BTreeMap::new(),
+            play_reminder: default_toggles(),
+            hijri: None,
+            holidays: vec![],
+            ramadan: BTreeMap::new(),
+            play_ramadan: default_ramadan_toggles(),
+            timezone: None,
+        }
+    }
+
+    // (re)compute iqamah times from the adhan timings
+    pub fn set_iqamah(&mut self, schedules: &HashMap<Prayer, IqamahSchedule>) {
+        let date = match chrono::NaiveDate::parse_from_str(&self.date, "%Y-%m-%d") {
+            Ok(date) => date,
+            Err(_) => return,
+        };
+        self.iqamah = self
+            .timings
+            .iter()
+            .filter_map(|(time, prayer)| {
+                let adhan = chrono::NaiveTime::parse_from_str(time, "%H:%M:%S").ok()?;
+                let iqamah = schedules.get(prayer)?.iqamah_time(date, adhan)?;
+                Some((iqamah.to_string(), *prayer))
+            })
+            .collect();
+    }
+
+    // (re)compute reminder times from minutes before each adhan; reminders that would fall on the
+    // previous day are skipped
+    pub fn set_reminders(&mut self, minutes_before: &HashMap<Prayer, i64>) {
+        self.reminders = self
+            .timings
+            .iter()
+            .filter_map(|(time, prayer)| {
+                let adhan = chrono::NaiveTime::parse_from_str(time, "%H:%M:%S").ok()?;
+                let minutes = minutes_before.get(prayer)?;
+                match adhan.overflowing_sub_signed(chrono::Duration::minutes(*minutes)) {
+                    (reminder, 0) => Some((reminder.to_string(), *prayer)),
+                    _ => None,
+                }
+            })
+            .collect();
+    }
+
+    // time of a prayer on this day
+    pub fn prayer_time(&self, prayer: Prayer) -> Option<chrono::NaiveTime> {
+        self.timings
+            .iter()
+            .find(|(_, p)| **p == prayer)
+            .and_then(|(time, _)| chrono::NaiveTime::parse_from_str(time, "%H:%M:%S").ok())
+    }
+
+    // whether ramadan alarms (and imsak) apply on this day
+    pub fn is_ramadan(&self, ramadan: &RamadanSettings) -> bool {
+        match ramadan.mode {
+            RamadanMode::Auto => self
+                .hijri
+                .as_ref()
+                .map(HijriDate::is_ramadan)
+                .unwrap_or(false),
+            RamadanMode::On => true,
+            RamadanMode::Off => false,
+        }
+    }
+
+    // (re)compute ramadan alarm times; cleared outside of ramadan
+    pub fn set_ramadan(&mut self, ramadan: &RamadanSettings) {
+        self.ramadan = BTreeMap::new();
+        if !self.is_ramadan(ramadan) {
+            return;
+        }
+
+        let suhoor = self
+            .prayer_time(Prayer::Imsak)
+            .or_else(|| self.prayer_time(Prayer::Fajr))
+            .map(|time| time.overflowing_sub_signed(chrono::Duration::minutes(ramadan.suhoor)));
+        if let Some((time, 0)) = suhoor {
+            self.ramadan.insert(time.to_string(), RamadanEvent::Suhoor);
+        }
+        if let Some(time) = self.prayer_time(Prayer::Maghrib).filter(|_| ramadan.iftar) {
+            self.ramadan.insert(time.to_string(), RamadanEvent::Iftar);
+        }
+        let taraweeh = ramadan.taraweeh.zip(self.prayer_time(Prayer::Isha));
+        if let Some((minutes, isha)) = taraweeh {
+            let time = isha.overflowing_add_signed(chrono::Duration::minutes(minutes));
+            if time.1 == 0 {
+                self.ramadan
+                    .insert(time.0.to_string(), RamadanEvent::Taraweeh);
+            }
+        }
+    }
 }
 
 impl std::fmt::Display for PrayerTime {
@@ -277,45 +641,67 @@ impl std::fmt::Display for PrayerTime {
     }
 }
 
-impl From<api::PrayerData> for PrayerTime {
-    fn from(prayer_data: api::PrayerData) -> Self {
-        let date = chrono::NaiveDate::parse_from_str(&prayer_data.date.gregorian.date, "%d-%m-%Y")
-            .unwrap();
+// the five prayers are required; other timings are skipped if missing
+impl TryFrom<api::PrayerData> for PrayerTime {
+    type Error = AdhanError;
 
-        let timestamp: u32 = prayer_data.date.timestamp.parse().unwrap();
-        let timings: BTreeMap<String, Prayer> = serde_json::from_value::<HashMap<String, String>>(
-            serde_json::to_value(prayer_data.timings).unwrap(),
-        )
-        .unwrap()
-        .iter()
-        .map(|(k, v)| {
-            let time = chrono::NaiveTime::parse_from_str(&v[..5], "%H:%M")
-                .unwrap()
-                .to_string();
-            (time, Prayer::from(k.as_str()))
-        })
-        .collect();
-
-        // create map of prayer adhan bools
-        let mut play_adhan = HashMap::new();
-        play_adhan.insert(Prayer::Fajr, true);
-        play_adhan.insert(Prayer::Dhuhr, true);
-        play_adhan.insert(Prayer::Asr, true);
-        play_adhan.insert(Prayer::Maghrib, true);
-        play_adhan.insert(Prayer::Isha, true);
+    fn try_from(prayer_data: api::PrayerData) -> Result<Self, Self::Error> {
+        let gregorian = &prayer_data.date.gregorian.date;
+        let date = chrono::NaiveDate::parse_from_str(gregorian, "%d-%m-%Y")
+            .map_err(|_| AdhanError::parse("date", gregorian))?;
 
-        PrayerTime {
-            date: date.to_string(),
-            timestamp,
-            timings,
-            play_adhan,
+        let timestamp: u32 = prayer_data
+            .date
+            .timestamp
+            .parse()
+            .map_err(|_| AdhanError::parse("timestamp", &prayer_data.date.timestamp))?;
+        let api_timings = prayer_data.timings;
+        let mut timings: BTreeMap<String, Prayer> = BTreeMap::new();
+        for (v, prayer) in [
+            (api_timings.imsak, Prayer::Imsak),
+            (api_timings.fajr, Prayer::Fajr),
+            (api_timings.sunrise, Prayer::Sunrise),
+            (api_timings.dhuhr, Prayer::Dhuhr),
+            (api_timings.asr, Prayer::Asr),
+            (api_timings.maghrib, Prayer::Maghrib),
+            (api_timings.isha, Prayer::Isha),
+            (api_timings.midnight, Prayer::Midnight),
+            (api_timings.lastthird, Prayer::LastThird),
+        ] {
+            if v.trim().is_empty() {
+                if prayer.is_prayer() {
+                    return Err(AdhanError::MissingTiming(prayer));
+                }
+                continue;
+            }
+            // "05:12 (NZDT)"; the zone suffix is the meta timezone's
+            let time = v
+                .split_whitespace()
+                .next()
+                .filter(|hh_mm| hh_mm.len() == 5)
+                .and_then(|hh_mm| chrono::NaiveTime::parse_from_str(hh_mm, "%H:%M").ok())
+                .ok_or_else(|| AdhanError::parse("time", &v))?;
+            timings.insert(time.to_string(), prayer);
         }
+
+        let mut prayer_time = PrayerTime::new(date, timestamp, timings);
+        prayer_time.hijri = HijriDate::try_from(&prayer_data.date.hijri)
+            .map_err(|e| tracing::error!("{}", e))
+            .ok();
+        prayer_time.holidays = prayer_data.date.hijri.holidays;
+        prayer_time.timezone = prayer_data
+            .meta
+            .timezone
+            .parse()
+            .map_err(|_| tracing::error!("unknown timezone {:?}", prayer_data.meta.timezone))
+            .ok();
+        Ok(prayer_time)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    // use super::*;
+    use super::*;
 
     #[test]
     fn test_date_last_month_of_year() {
@@ -327,4 +713,197 @@ mod tests {
             chrono::NaiveDate::parse_from_str("01-01-2021", "%d-%m-%Y").unwrap()
         );
     }
+
+    #[test]
+    fn test_iqamah_schedule() {
+        let schedule: IqamahSchedule = serde_json::from_str(
+            r#"{"time": {"offset": 20}, "weekdays": {"Fri": {"fixed": "13:30:00"}}}"#,
+        )
+        .unwrap();
+        let adhan = chrono::NaiveTime::from_hms_opt(13, 5, 0).unwrap();
+
+        // thursday: offset from adhan
+        let thursday = chrono::NaiveDate::from_ymd_opt(2022, 12, 29).unwrap();
+        assert_eq!(
+            schedule.iqamah_time(thursday, adhan),
+            chrono::NaiveTime::from_hms_opt(13, 25, 0)
+        );
+        // friday: fixed override
+        let friday = chrono::NaiveDate::from_ymd_opt(2022, 12, 30).unwrap();
+        assert_eq!(
+            schedule.iqamah_time(friday, adhan),
+            chrono::NaiveTime::from_hms_opt(13, 30, 0)
+        );
+        // past midnight: no iqamah that day
+        let isha = chrono::NaiveTime::from_hms_opt(21, 30, 0).unwrap();
+        assert_eq!(
+            schedule.iqamah_time(thursday, isha),
+            Some(chrono::NaiveTime::from_hms_opt(21, 50, 0).unwrap())
+        );
+        let late: IqamahSchedule = serde_json::from_str(r#"{"time": {"offset": 180}}"#).unwrap();
+        assert!(late.validate().is_ok());
+        assert_eq!(late.iqamah_time(thursday, isha), None);
+        let early: IqamahSchedule = serde_json::from_str(r#"{"time": {"offset": -10}}"#).unwrap();
+        assert!(early.validate().is_err());
+
+        let mut timings = BTreeMap::new();
+        timings.insert(adhan.to_string(), Prayer::Dhuhr);
+        timings.insert("16:49:00".to_owned(), Prayer::Asr);
+        let mut prayer_time = PrayerTime::new(friday, 0, timings);
+        prayer_time.set_iqamah(&HashMap::from([(Prayer::Dhuhr, schedule)]));
+        assert_eq!(
+            prayer_time.iqamah,
+            BTreeMap::from([("13:30:00".to_owned(), Prayer::Dhuhr)])
+        );
+    }
+
+    #[test]
+    fn test_from_api_prayer_data() {
+        let prayer_data: api::PrayerData = serde_json::from_str(
+            r#"{
+                "timings": {
+                    "Fajr": "04:40 (NZDT)", "Sunrise": "06:16 (NZDT)", "Dhuhr": "13:05 (NZDT)",
+                    "Asr": "16:49 (NZDT)", "Sunset": "19:53 (NZDT)", "Maghrib": "19:53 (NZDT)",
+                    "Isha": "21:24 (NZDT)", "Imsak": "04:30 (NZDT)", "Midnight": "01:05 (NZDT)",
+                    "Firstthird": "23:21 (NZDT)", "Lastthird": "02:49 (NZDT)"
+                },
+                "date": {
+                    "readable": "01 Nov 2022",
+                    "timestamp": "1667246461",
+                    "gregorian": {
+                        "date": "01-11-2022", "format": "DD-MM-YYYY", "day": "01",
+                        "weekday": {"en": "Tuesday"}, "month": {"number": 11, "en": "November"},
+                        "year": "2022"
+                    },
+                    "hijri": {
+                        "date": "06-04-1444", "format": "DD-MM-YYYY", "day": "06",
+                        "weekday": {"en": "Al Thalaata", "ar": "الثلاثاء"},
+                        "month": {"number": 4, "en": "Rabīʿ al-thānī", "ar": "رَبيع الثاني"},
+                        "year": "1444",
+                        "designation": {"abbreviated": "AH", "expanded": "Anno Hegirae"},
+                        "holidays": []
+                    }
+                },
+                "meta": {
+                    "latitude": -36.8484597,
+                    "longitude": 174.7633315,
+                    "timezone": "Pacific/Auckland",
+                    "offset": {
+                        "Imsak": 0, "Fajr": 0, "Sunrise": 0, "Dhuhr": 0, "Asr": 0,
+                        "Maghrib": -2, "Sunset": 0, "Isha": 0, "Midnight": 0
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let prayer_time = PrayerTime::try_from(prayer_data.clone()).unwrap();
+        assert_eq!(prayer_time.date, "2022-11-01");
+        assert_eq!(prayer_data.meta.offset.maghrib, -2);
+        assert_eq!(prayer_time.timings.len(), 9);
+        assert_eq!(prayer_time.timings.get("06:16:00"), Some(&Prayer::Sunrise));
+        assert_eq!(
+            prayer_time.timings.get("02:49:00"),
+            Some(&Prayer::LastThird)
+        );
+        assert_eq!(prayer_time.timezone, Some(chrono_tz::Pacific::Auckland));
+        // only the prayers are enabled by default
+        assert_eq!(prayer_time.play_adhan.get(&Prayer::Fajr), Some(&true));
+        assert_eq!(prayer_time.play_adhan.get(&Prayer::Sunrise), Some(&false));
+        assert_eq!(
+            prayer_time.hijri,
+            Some(HijriDate {
+                day: 6,
+                month: 4,
+                year: 1444,
+                month_name: "Rabīʿ al-thānī".to_owned()
+            })
+        );
+
+        // only the hijri date is required
+        let hijri: api::Hijri = serde_json::from_str(
+            r#"{"date": "06-04-1444", "day": "06", "month": {"number": 4, "en": "Rabīʿ al-thānī"}, "year": "1444"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            HijriDate::try_from(&hijri),
+            Ok(prayer_time.hijri.clone().unwrap())
+        );
+        assert!(hijri.holidays.is_empty());
+
+        // malformed or missing fields are errors, except for the optional timings
+        let mut invalid = prayer_data.clone();
+        invalid.timings.midnight = String::new();
+        invalid.timings.isha = String::new();
+        assert!(matches!(
+            PrayerTime::try_from(invalid.clone()),
+            Err(AdhanError::MissingTiming(Prayer::Isha))
+        ));
+        invalid.timings.isha = "19:5".to_owned();
+        assert!(matches!(
+            PrayerTime::try_from(invalid.clone()),
+            Err(AdhanError::Parse { field: "time", .. })
+        ));
+        invalid.timings.isha = prayer_data.timings.isha.clone();
+        assert_eq!(PrayerTime::try_from(invalid).unwrap().timings.len(), 8);
+
+        // times without a zone suffix; an unknown zone is left out
+        let mut unzoned = prayer_data.clone();
+        unzoned.timings.fajr = "04:40".to_owned();
+        unzoned.meta.timezone = "Mars/Olympus_Mons".to_owned();
+        let prayer_time = PrayerTime::try_from(unzoned).unwrap();
+        assert_eq!(prayer_time.timings.get("04:40:00"), Some(&Prayer::Fajr));
+        assert_eq!(prayer_time.timezone, None);
+
+        let mut invalid = prayer_data;
+        invalid.date.gregorian.date = "2022-11-01".to_owned();
+        assert!(PrayerTime::try_from(invalid).is_err());
+    }
+
+    #[test]
+    fn test_ramadan() {
+        let mut timings = BTreeMap::new();
+        timings.insert("04:30:00".to_owned(), Prayer::Imsak);
+        timings.insert("04:40:00".to_owned(), Prayer::Fajr);
+        timings.insert("19:53:00".to_owned(), Prayer::Maghrib);
+        timings.insert("21:24:00".to_owned(), Prayer::Isha);
+        let date = chrono::NaiveDate::from_ymd_opt(2023, 3, 23).unwrap();
+        let mut prayer_time = PrayerTime::new(date, 0, timings);
+        let settings = RamadanSettings {
+            taraweeh: Some(30),
+            ..RamadanSettings::default()
+        };
+
+        // not ramadan (and unknown hijri date)
+        prayer_time.set_ramadan(&settings);
+        assert!(prayer_time.ramadan.is_empty());
+
+        prayer_time.hijri = Some(crate::hijri::from_gregorian(date, 0));
+        prayer_time.set_ramadan(&settings);
+        assert_eq!(
+            prayer_time.ramadan,
+            BTreeMap::from([
+                ("03:45:00".to_owned(), RamadanEvent::Suhoor),
+                ("19:53:00".to_owned(), RamadanEvent::Iftar),
+                ("21:54:00".to_owned(), RamadanEvent::Taraweeh),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_reminders() {
+        let mut timings = BTreeMap::new();
+        timings.insert("00:05:00".to_owned(), Prayer::Isha);
+        timings.insert("04:45:00".to_owned(), Prayer::Fajr);
+        timings.insert("13:05:00".to_owned(), Prayer::Dhuhr);
+        let date = chrono::NaiveDate::from_ymd_opt(2022, 6, 1).unwrap();
+        let mut prayer_time = PrayerTime::new(date, 0, timings);
+
+        prayer_time.set_reminders(&HashMap::from([(Prayer::Fajr, 10), (Prayer::Isha, 10)]));
+        // no reminder for dhuhr; isha reminder would be on the previous day
+        assert_eq!(
+            prayer_time.reminders,
+            BTreeMap::from([("04:35:00".to_owned(), Prayer::Fajr)])
+        );
+    }
 }
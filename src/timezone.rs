@@ -0,0 +1,106 @@
+// Timezone of the location: prayer timings are wall clock times there, whatever the zone of the
+// machine (a docker container usually runs in UTC).
+use chrono::{Offset, TimeZone};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Zone {
+    Iana(chrono_tz::Tz),
+    // zone of the machine, if the location's is unknown
+    System,
+}
+
+impl Zone {
+    // the machine's zone, by name if `TZ` is set
+    pub fn system() -> Self {
+        std::env::var("TZ")
+            .ok()
+            .and_then(|tz| tz.parse().ok())
+            .map(Zone::Iana)
+            .unwrap_or(Zone::System)
+    }
+
+    pub fn name(&self) -> Option<String> {
+        match self {
+            Zone::Iana(tz) => Some(tz.name().to_owned()),
+            Zone::System => None,
+        }
+    }
+
+    // wall clock time at instant
+    pub fn local(&self, instant: chrono::DateTime<chrono::Utc>) -> chrono::NaiveDateTime {
+        self.with_offset(instant).naive_local()
+    }
+
+    pub fn with_offset(
+        &self,
+        instant: chrono::DateTime<chrono::Utc>,
+    ) -> chrono::DateTime<chrono::FixedOffset> {
+        match self {
+            Zone::Iana(tz) => instant.with_timezone(tz).fixed_offset(),
+            Zone::System => instant.with_timezone(&chrono::Local).fixed_offset(),
+        }
+    }
+
+    // instant of wall clock time; the first of times repeated when clocks go back, and times
+    // skipped when clocks go forward are moved forward as well (01:30 is 02:30 if 01:00 is 02:00)
+    pub fn instant(&self, local: chrono::NaiveDateTime) -> chrono::DateTime<chrono::Utc> {
+        match self {
+            Zone::Iana(tz) => instant(tz, local),
+            Zone::System => instant(&chrono::Local, local),
+        }
+    }
+}
+
+fn instant<Z: TimeZone>(zone: &Z, local: chrono::NaiveDateTime) -> chrono::DateTime<chrono::Utc> {
+    if let Some(instant) = zone.from_local_datetime(&local).earliest() {
+        return instant.with_timezone(&chrono::Utc);
+    }
+    // in a gap: use the offset from before the clocks changed
+    let offset = zone
+        .from_local_datetime(&(local - chrono::Duration::hours(3)))
+        .earliest()
+        .map(|before| before.offset().fix())
+        .unwrap_or(chrono::Utc.fix());
+    chrono::Utc.from_utc_datetime(&(local - offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_daylight_saving() {
+        let london = Zone::Iana(chrono_tz::Europe::London);
+        let datetime = |s| chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+        let utc = |s| chrono::Utc.from_utc_datetime(&datetime(s));
+
+        // clocks go forward at 01:00 GMT
+        assert_eq!(
+            london.instant(datetime("2026-03-29 00:30")),
+            utc("2026-03-29 00:30")
+        );
+        assert_eq!(
+            london.instant(datetime("2026-03-29 01:30")),
+            utc("2026-03-29 01:30")
+        );
+        assert_eq!(
+            london.instant(datetime("2026-03-29 04:40")),
+            utc("2026-03-29 03:40")
+        );
+        assert_eq!(
+            london.local(utc("2026-03-29 01:30")),
+            datetime("2026-03-29 02:30")
+        );
+
+        // clocks go back at 02:00 BST; 01:30 happens twice
+        assert_eq!(
+            london.instant(datetime("2026-10-25 01:30")),
+            utc("2026-10-25 00:30")
+        );
+        assert_eq!(
+            london.instant(datetime("2026-10-25 04:40")),
+            utc("2026-10-25 04:40")
+        );
+        assert_eq!(london.name(), Some("Europe/London".to_owned()));
+    }
+}